@@ -26,6 +26,7 @@ run_as_user() {
 
   run_cmd "mkdir -p $HOME/$APP_DIR/config"       "$SUB_SCRIPT_LOG" "could not create $HOME/$APP_DIR/config..."
   run_cmd "mkdir -p $HOME/$APP_DIR/logs"         "$SUB_SCRIPT_LOG" "could not create $HOME/$APP_DIR/logs..."
+  run_cmd "mkdir -p $HOME/$APP_DIR/history"      "$SUB_SCRIPT_LOG" "could not create $HOME/$APP_DIR/history..."
   run_cmd "mkdir -p $HOME/$APP_DIR/last_version" "$SUB_SCRIPT_LOG" "could not create $HOME/$APP_DIR/last_version..."
   run_cmd "cd $DEV_DIR/$REPO_NAME"               "$SUB_SCRIPT_LOG" "could not change directory to $DEV_DIR/$REPO_NAME..."
 
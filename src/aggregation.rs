@@ -0,0 +1,345 @@
+use std::str::FromStr;
+use chrono::{Datelike, Months, NaiveDate, TimeDelta};
+use serde::Serialize;
+use thiserror::Error;
+use crate::export::ExportRow;
+
+/// Max number of days a single aggregation may cover
+const MAX_DAYS: i64 = 3700;
+
+/// Period to aggregate energy and cost per
+///
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl FromStr for Period {
+    type Err = AggregationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            "year" => Ok(Period::Year),
+            _ => Err(AggregationError::PeriodError(s.to_string())),
+        }
+    }
+}
+
+impl Period {
+    /// Returns the first day of the period the given date belongs to, weeks start on Mondays
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the date to get the period start for
+    pub fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - TimeDelta::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => date.with_day(1).unwrap_or(date),
+            Period::Year => date.with_ordinal(1).unwrap_or(date),
+        }
+    }
+
+    /// Returns the last day of the period starting at the given date
+    ///
+    /// # Arguments
+    ///
+    /// * 'start' - first day of the period
+    pub fn end(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start,
+            Period::Week => start + TimeDelta::days(6),
+            Period::Month => start + Months::new(1) - TimeDelta::days(1),
+            Period::Year => start + Months::new(12) - TimeDelta::days(1),
+        }
+    }
+
+    /// Returns a label for the period starting at the given date
+    ///
+    /// # Arguments
+    ///
+    /// * 'start' - first day of the period
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => format!("{}-W{:02}", start.iso_week().year(), start.iso_week().week()),
+            Period::Month => start.format("%Y-%m").to_string(),
+            Period::Year => start.format("%Y").to_string(),
+        }
+    }
+
+    /// Returns the default range to aggregate over when none is given, i.e. the last 31 days,
+    /// 13 weeks, 12 months or 5 years including the current one
+    ///
+    /// # Arguments
+    ///
+    /// * 'today' - today's local date
+    pub fn default_range(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let from = match self {
+            Period::Day => today - TimeDelta::days(30),
+            Period::Week => self.start(today) - TimeDelta::weeks(12),
+            Period::Month => self.start(today) - Months::new(11),
+            Period::Year => self.start(today) - Months::new(48),
+        };
+
+        (from, today)
+    }
+}
+
+/// Energy and money summed up over a number of 15-minute intervals
+///
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct EnergyTotals {
+    pub imported_kwh: f64,
+    pub exported_kwh: f64,
+    pub cost: f64,
+    pub revenue: f64,
+    pub self_consumed_kwh: f64,
+    pub self_consumed_value: f64,
+}
+
+impl EnergyTotals {
+    /// Adds an interval. Self-consumed solar is what was produced but not exported, and its
+    /// value is what it would have cost to import it instead
+    ///
+    /// # Arguments
+    ///
+    /// * 'row' - the interval to add
+    pub fn add(&mut self, row: &ExportRow) {
+        let self_consumed = (row.production_kwh - row.exported_kwh).max(0.0);
+
+        self.imported_kwh += row.imported_kwh;
+        self.exported_kwh += row.exported_kwh;
+        self.cost += row.cost;
+        self.revenue += row.revenue;
+        self.self_consumed_kwh += self_consumed;
+        self.self_consumed_value += self_consumed * row.tariff_buy;
+    }
+
+    /// Adds totals from another instance
+    ///
+    /// # Arguments
+    ///
+    /// * 'other' - the totals to add
+    pub fn add_totals(&mut self, other: &EnergyTotals) {
+        self.imported_kwh += other.imported_kwh;
+        self.exported_kwh += other.exported_kwh;
+        self.cost += other.cost;
+        self.revenue += other.revenue;
+        self.self_consumed_kwh += other.self_consumed_kwh;
+        self.self_consumed_value += other.self_consumed_value;
+    }
+
+    /// Returns a copy with all values rounded to two decimals
+    ///
+    pub fn rounded(&self) -> EnergyTotals {
+        let round = |v: f64| (v * 100.0).round() / 100.0;
+
+        EnergyTotals {
+            imported_kwh: round(self.imported_kwh),
+            exported_kwh: round(self.exported_kwh),
+            cost: round(self.cost),
+            revenue: round(self.revenue),
+            self_consumed_kwh: round(self.self_consumed_kwh),
+            self_consumed_value: round(self.self_consumed_value),
+        }
+    }
+}
+
+/// Totals for one period
+///
+#[derive(Serialize)]
+pub struct Bucket {
+    pub label: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub days_with_data: u32,
+    pub totals: EnergyTotals,
+}
+
+/// Result of an aggregation
+///
+#[derive(Serialize)]
+pub struct Aggregation {
+    pub period: Period,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub buckets: Vec<Bucket>,
+    pub total: EnergyTotals,
+}
+
+/// Checks that the given range is valid and not too long
+///
+/// # Arguments
+///
+/// * 'from' - first day of the range
+/// * 'to' - last day of the range
+pub fn check_range(from: NaiveDate, to: NaiveDate) -> Result<(), AggregationError> {
+    if to < from || (to - from).num_days() >= MAX_DAYS {
+        return Err(AggregationError::RangeError(format!("{} - {}", from, to)));
+    }
+
+    Ok(())
+}
+
+/// Builds an aggregation from day totals, days are put in buckets per period and buckets
+/// without any data are kept so that charts get a continuous axis
+///
+/// # Arguments
+///
+/// * 'period' - the period to aggregate per
+/// * 'from' - first day of the range
+/// * 'to' - last day of the range
+/// * 'days' - totals per day with data, in date order
+pub fn aggregate(period: Period, from: NaiveDate, to: NaiveDate, days: &[(NaiveDate, EnergyTotals)]) -> Aggregation {
+    let mut buckets: Vec<Bucket> = Vec::new();
+    let mut total = EnergyTotals::default();
+
+    let mut start = period.start(from);
+    while start <= to {
+        let end = period.end(start);
+        let mut bucket = Bucket {
+            label: period.label(start),
+            from: start,
+            to: end,
+            days_with_data: 0,
+            totals: EnergyTotals::default(),
+        };
+
+        days.iter()
+            .filter(|(date, _)| *date >= start && *date <= end && *date >= from && *date <= to)
+            .for_each(|(_, totals)| {
+                bucket.days_with_data += 1;
+                bucket.totals.add_totals(totals);
+            });
+
+        total.add_totals(&bucket.totals);
+        bucket.totals = bucket.totals.rounded();
+        buckets.push(bucket);
+
+        start = end + TimeDelta::days(1);
+    }
+
+    Aggregation { period, from, to, buckets, total: total.rounded() }
+}
+
+#[derive(Debug, Error)]
+pub enum AggregationError {
+    #[error("PeriodError: {0}")]
+    PeriodError(String),
+    #[error("RangeError: {0}")]
+    RangeError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn totals(imported_kwh: f64, cost: f64) -> EnergyTotals {
+        EnergyTotals { imported_kwh, cost, ..EnergyTotals::default() }
+    }
+
+    #[test]
+    fn period_bounds_and_labels() {
+        let wednesday = date(2025, 10, 22);
+
+        assert_eq!(Period::Week.start(wednesday), date(2025, 10, 20));
+        assert_eq!(Period::Week.end(date(2025, 10, 20)), date(2025, 10, 26));
+        assert_eq!(Period::Week.label(date(2025, 10, 20)), "2025-W43");
+        assert_eq!(Period::Month.start(wednesday), date(2025, 10, 1));
+        assert_eq!(Period::Month.end(date(2024, 2, 1)), date(2024, 2, 29));
+        assert_eq!(Period::Month.label(date(2024, 2, 1)), "2024-02");
+        assert_eq!(Period::Year.start(wednesday), date(2025, 1, 1));
+        assert_eq!(Period::Year.end(date(2025, 1, 1)), date(2025, 12, 31));
+        assert_eq!(Period::Day.label(wednesday), "2025-10-22");
+    }
+
+    #[test]
+    fn week_label_uses_iso_year() {
+        assert_eq!(Period::Week.label(date(2024, 12, 30)), "2025-W01");
+    }
+
+    #[test]
+    fn default_ranges_end_today() {
+        let today = date(2025, 10, 22);
+
+        assert_eq!(Period::Day.default_range(today), (date(2025, 9, 22), today));
+        assert_eq!(Period::Week.default_range(today), (date(2025, 7, 28), today));
+        assert_eq!(Period::Month.default_range(today), (date(2024, 11, 1), today));
+        assert_eq!(Period::Year.default_range(today), (date(2021, 1, 1), today));
+    }
+
+    #[test]
+    fn period_from_str() {
+        assert!("month".parse::<Period>().unwrap() == Period::Month);
+        assert!(matches!("fortnight".parse::<Period>(), Err(AggregationError::PeriodError(_))));
+    }
+
+    #[test]
+    fn range_checked() {
+        assert!(check_range(date(2025, 10, 1), date(2025, 10, 1)).is_ok());
+        assert!(matches!(check_range(date(2025, 10, 2), date(2025, 10, 1)), Err(AggregationError::RangeError(_))));
+        assert!(check_range(date(2015, 1, 1), date(2025, 10, 1)).is_err());
+    }
+
+    #[test]
+    fn self_consumption_from_interval() {
+        let from = Local.with_ymd_and_hms(2025, 10, 22, 12, 0, 0).unwrap();
+        let row = |production_kwh: f64, exported_kwh: f64| ExportRow {
+            from,
+            to: from + TimeDelta::minutes(15),
+            tariff_buy: 2.0,
+            tariff_sell: 1.0,
+            imported_kwh: 0.1,
+            exported_kwh,
+            cost: 0.2,
+            revenue: exported_kwh,
+            production_kwh,
+            load_kwh: 0.5,
+            soc: None,
+        };
+
+        let mut totals = EnergyTotals::default();
+        totals.add(&row(1.0, 0.25));
+        // Exporting more than produced (from the battery) doesn't count as negative self-consumption
+        totals.add(&row(0.0, 0.5));
+
+        assert_eq!(totals.self_consumed_kwh, 0.75);
+        assert_eq!(totals.self_consumed_value, 1.5);
+        assert_eq!(totals.exported_kwh, 0.75);
+        assert_eq!(totals.imported_kwh, 0.2);
+    }
+
+    #[test]
+    fn aggregate_keeps_empty_buckets_and_clips_to_range() {
+        let days = [
+            (date(2025, 9, 30), totals(100.0, 100.0)),
+            (date(2025, 10, 1), totals(1.004, 2.0)),
+            (date(2025, 10, 2), totals(2.0, 3.0)),
+            (date(2025, 10, 20), totals(4.0, 5.0)),
+        ];
+
+        // The first week starts on Monday 2025-09-29, before the range
+        let aggregation = aggregate(Period::Week, date(2025, 10, 1), date(2025, 10, 21), &days);
+
+        let labels = aggregation.buckets.iter().map(|b| b.label.as_str()).collect::<Vec<&str>>();
+        assert_eq!(labels, vec!["2025-W40", "2025-W41", "2025-W42", "2025-W43"]);
+        assert_eq!(aggregation.buckets.iter().map(|b| b.days_with_data).collect::<Vec<u32>>(), vec![2, 0, 0, 1]);
+        assert_eq!(aggregation.buckets[0].totals.imported_kwh, 3.0);
+        assert_eq!(aggregation.buckets[1].totals.imported_kwh, 0.0);
+        assert_eq!(aggregation.total.imported_kwh, 7.0);
+        assert_eq!(aggregation.total.cost, 10.0);
+    }
+}
@@ -1,25 +1,106 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
 use std::ops::Add;
-use chrono::{DateTime, Duration, DurationRound, Local, NaiveDate, TimeDelta, Timelike, Utc};
-use tracing::{error, info};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Instant;
+use chrono::{DateTime, Datelike, Duration, DurationRound, Local, NaiveDate, TimeDelta, Timelike, Utc};
+use tracing::{error, info, warn};
 use serde::Serialize;
 use anyhow::{Result, anyhow, Context};
 use tokio::select;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use crate::initialization::Config;
+use tokio::sync::{watch, Notify};
+use crate::initialization::{Config, LocalTariffFees, PowerPeak, UsagePolicy};
+use crate::manager_history::HistoryStore;
+use crate::manager_history::models::{DayHistory, StoredInterval, StoredSample};
 use crate::manager_inverter::Inverter;
-use crate::manager_mygrid::{get_base_data, get_schedule};
+use crate::manager_inverter::models::{EnergyIntervals, Samples};
+use crate::manager_mygrid::{empty_mygrid_data, find_base_data, get_schedule};
 use crate::manager_mygrid::models::Block;
+use crate::manager_mqtt::MqttPublisher;
+use crate::manager_mqtt::models::MqttState;
 use crate::manager_nordpool::NordPool;
+use crate::manager_nordpool::provider::get_spot_price_provider;
+use crate::manager_notifications::Notifier;
+use crate::manager_notifications::events::EventTracker;
+use crate::manager_notifications::models::EventKind;
 use crate::manager_weather::Weather;
+use crate::aggregation::{aggregate, check_range, Aggregation, EnergyTotals, Period};
+use crate::export::{to_export_rows, ExportRow};
+use crate::forecast_accuracy::{get_day_accuracy, get_trend, AccuracyTrend, DayAccuracy, DEFAULT_TREND_DAYS};
+use crate::health::HealthState;
+use crate::metrics::{Metrics, MetricsText};
+use crate::recommender::{recommend, Appliance, PlanningData, Recommendation};
+use crate::savings::{get_savings, Savings};
+use crate::schedule_tracking::{track_schedule, ScheduleSummary};
+use crate::sources::{DayAheadPoll, Sources};
+use crate::tariff_model::{get_tariff_model, select_tariff_fees, FeeSource};
+use crate::power_peak::{get_peak, get_peak_status, max_peak};
 use crate::models::{DataItem, DataPoint, HistoryData, MygridData, RealTimeData, Series, TariffColor, TariffFees, TwoDayMinMax, WeatherData};
-use crate::usage_policy::get_policy;
+use crate::usage_policy::{apply_peak_warning, get_policy, get_policy_timeline, get_thresholds, PolicyDecision, PolicyReason, PolicyRules, Thresholds};
 
-pub enum Cmd {
-    SmallDashData,
-    FullDashData,
+/// Max number of complete past days kept in memory by the archive
+const MAX_CACHED_DAYS: usize = 31;
+
+/// Dash types served by the web server
+#[derive(Clone, Copy)]
+pub enum DashType {
+    Small,
+    Full,
+}
+
+/// Pre-serialized live dash data, replaced as a whole by the dispatcher after each update,
+/// together with what appliance recommendations are searched in
+pub struct DashSnapshot {
+    pub small: String,
+    pub full: String,
+    pub planning: PlanningData,
+}
+
+impl DashSnapshot {
+    /// Returns the json for the given dash type
+    ///
+    /// # Arguments
+    ///
+    /// * 'dash_type' - the dash type to get json for
+    pub fn get(&self, dash_type: DashType) -> &String {
+        match dash_type {
+            DashType::Small => &self.small,
+            DashType::Full => &self.full,
+        }
+    }
+}
+
+/// Channel through which the dispatcher publishes snapshots, handlers borrow the latest
+/// snapshot directly from the sender while push subscribers hold receivers
+pub type Snapshots = Arc<watch::Sender<Option<Arc<DashSnapshot>>>>;
+
+/// Keeps track of when dash data was last requested, so that upstream sources are only polled
+/// while someone is looking at the dashes
+#[derive(Default)]
+pub struct Activity {
+    last_request: AtomicI64,
+    wake: Notify,
 }
 
+impl Activity {
+    /// Registers a request for live data, waking up the dispatcher if it has been idle
+    ///
+    pub fn request(&self) {
+        let timestamp = Utc::now().timestamp();
+        let previous = self.last_request.swap(timestamp, Ordering::Relaxed);
+
+        if timestamp - previous > 60 {
+            self.wake.notify_one();
+        }
+    }
+
+    /// Returns whether live data has been requested within the last 30 minutes
+    ///
+    fn is_active(&self) -> bool {
+        Utc::now().timestamp() - self.last_request.load(Ordering::Relaxed) <= 1800
+    }
+}
 
 /// Sync start point
 /// This loop will never end unless some means of stopping it is implemented,but rather
@@ -27,11 +108,13 @@ pub enum Cmd {
 ///
 /// # Arguments
 ///
-/// * 'tx' - mpsc sender to the web server
-/// * 'rx' - mpsc receiver from the web server
+/// * 'snapshots' - channel to publish live dash data through
+/// * 'activity' - tracker of requests from the web server
+/// * 'health' - health state to report to
+/// * 'metrics' - metrics to report to
 /// * 'config' - configuration struct
-pub async fn run(tx: UnboundedSender<String>,  rx: UnboundedReceiver<Cmd>, config: &Config) {
-    let mut disp = match Dispatcher::new(config).await {
+pub async fn run(snapshots: Snapshots, activity: Arc<Activity>, health: Arc<HealthState>, metrics: Arc<Metrics>, config: &Config) {
+    let mut disp = match Dispatcher::new(snapshots, activity, health, metrics, config).await {
         Ok(d) => d,
         Err(e) => {
             error!("while initializing dispatcher: {:?}", e);
@@ -39,14 +122,14 @@ pub async fn run(tx: UnboundedSender<String>,  rx: UnboundedReceiver<Cmd>, confi
         }
     };
 
-    if let Err(e) = &disp.check_updates(true).await {
-        error!("while checking for updates: {:?}", e);
-    }
-    if let Err(e) = &disp.update_mygrid_data().await {
-        error!("while updating mygrid data: {:?}", e);
-    }
+    disp.health.dispatcher_tick();
+    disp.check_updates(true).await;
+    disp.update_mygrid_data().await;
+    disp.check_events();
+    disp.publish_mqtt();
+    disp.publish_snapshot();
 
-    match dispatch_loop(tx, rx, &mut disp).await {
+    match dispatch_loop(&mut disp).await {
         Ok(_) => {
             info!("dispatch loop terminated");
         },
@@ -56,10 +139,13 @@ pub async fn run(tx: UnboundedSender<String>,  rx: UnboundedReceiver<Cmd>, confi
     }
 }
 
-/// Main dispatch loop that regularly read mygrid files and builds up history data
-/// while also listening for requests from the web server
+/// Main dispatch loop that regularly read mygrid files and builds up history data,
+/// while also waking up early when live data is requested after a period of inactivity.
+///
+/// Failing sources don't end the loop, they are retried with backoff while the last good
+/// data from them is kept
 ///
-async fn dispatch_loop(tx: UnboundedSender<String>, mut rx: UnboundedReceiver<Cmd>, disp: &mut Dispatcher) -> Result<()> {
+async fn dispatch_loop(disp: &mut Dispatcher) -> Result<()> {
     let (tx_sleep, mut rx_sleep) = tokio::sync::mpsc::unbounded_channel::<bool>();
     tokio::spawn(async move {
         loop {
@@ -67,28 +153,26 @@ async fn dispatch_loop(tx: UnboundedSender<String>, mut rx: UnboundedReceiver<Cm
             tx_sleep.send(true).unwrap();
         }
     });
-    
+    let activity = disp.activity.clone();
+
     loop {
         select! {
-            cmd = rx.recv() => {
-                if let Some(cmd) = cmd {
-                    let _ = &disp.check_updates(true).await?;
-
-                    let data = disp.execute_cmd(cmd).await?;
-                    tx.send(data).context("failed to send command response")?;
-                } else {
-                    return Err(anyhow!("cmd receiver closed unexpectedly"));
-                }
+            _ = activity.wake.notified() => {
+                disp.health.dispatcher_tick();
+                disp.check_updates(false).await;
             },
             wake = rx_sleep.recv() => {
                 if wake.is_some() {
-                    let _ = &disp.check_updates(false).await?;
-                    let _ = &disp.update_mygrid_data().await?;
+                    disp.health.dispatcher_tick();
+                    disp.check_updates(false).await;
+                    disp.update_mygrid_data().await;
+                    disp.check_events();
+                    disp.publish_mqtt();
+                    disp.publish_snapshot();
                 } else {
                     return Err(anyhow!("wake receiver closed unexpectedly"));
                 }
             },
-            else => return Ok(()),
         }
     }
 }
@@ -102,75 +186,219 @@ struct Dispatcher {
     inverter: Inverter,
     weather: Weather,
     nordpool: NordPool,
+    tariff_fees: Option<LocalTariffFees>,
+    fee_source: Option<FeeSource>,
+    refetch_tariffs: bool,
+    tomorrow_poll: DayAheadPoll,
+    history: HistoryStore,
     schedule_path: String,
     base_data_path: String,
+    history_date: Option<NaiveDate>,
+    stored_samples: Vec<StoredSample>,
+    today_intervals: Vec<StoredInterval>,
+    unpriced_intervals: Vec<EnergyIntervals>,
     history_data: HistoryData,
+    snapshots: Snapshots,
+    activity: Arc<Activity>,
+    health: Arc<HealthState>,
+    metrics: Arc<Metrics>,
+    sources: Sources,
+    notifier: Notifier,
+    event_tracker: EventTracker,
+    mqtt: Option<MqttPublisher>,
     real_time_data: RealTimeData,
     weather_data: WeatherData,
     today_tariffs: Option<Vec<DataItem<f64>>>,
     tomorrow_tariffs: Option<Vec<DataItem<f64>>>,
     today_tariffs_sell: Option<HashMap<DateTime<Utc>,f64>>,
+    tomorrow_tariffs_sell: Option<HashMap<DateTime<Utc>,f64>>,
     policy_tariffs: HashMap<DateTime<Utc>, f64>,
     max_tariff: u8,
     today_bought: f64,
     today_sold: f64,
     exported_energy: f64,
     imported_energy: f64,
+    savings: Savings,
+    schedule_summary: ScheduleSummary,
+    forecast_accuracy: Option<DayAccuracy>,
     usage_policy: TariffColor,
-    last_request: i64,
+    policy_reason: Option<PolicyReason>,
+    policy_timeline: Vec<DataItem<PolicyDecision>>,
+    policy_config: UsagePolicy,
+    power_peak: PowerPeak,
+    month_peak: Option<DataItem<f64>>,
+    appliances: Vec<Appliance>,
     last_update: i64,
     last_policy_update: DateTime<Utc>,
     time_delta: TimeDelta,
     version: String,
 }
 
+/// References to all data making up one day in the dashboards, either live or historic
+///
+struct DashView<'a> {
+    date: NaiveDate,
+    policy: Option<TariffColor>,
+    policy_reason: Option<&'a PolicyReason>,
+    policy_timeline: Option<&'a Vec<DataItem<PolicyDecision>>>,
+    weather_data: &'a WeatherData,
+    real_time_data: Option<&'a RealTimeData>,
+    tariffs_buy: Option<&'a Vec<DataItem<f64>>>,
+    tariffs_buy_tomorrow: Option<&'a Vec<DataItem<f64>>>,
+    max_tariff: u8,
+    thresholds: Thresholds,
+    thresholds_tomorrow: Thresholds,
+    fee_source: Option<FeeSource>,
+    schedule: &'a Vec<Block>,
+    mygrid_data: &'a MygridData,
+    history_data: &'a HistoryData,
+    sold: f64,
+    bought: f64,
+    exported: f64,
+    imported: f64,
+    savings: Savings,
+    schedule_summary: &'a ScheduleSummary,
+    forecast_accuracy: Option<&'a DayAccuracy>,
+    recommendations: Option<&'a Vec<Recommendation>>,
+    sources: Option<&'a Sources>,
+    time_delta: TimeDelta,
+    version: &'a str,
+}
+
+/// Buy and sell tariffs for one day
+type DayTariffs = (Vec<DataItem<f64>>, Vec<DataItem<f64>>);
+
+/// MyGrid base data, tariffs and energy data for one day
+///
+struct EnergyDay {
+    mygrid_data: Option<MygridData>,
+    tariffs: Option<DayTariffs>,
+    fee_source: Option<FeeSource>,
+    samples: Vec<StoredSample>,
+    intervals: Vec<StoredInterval>,
+}
+
+/// Data collected for a past day
+///
+struct HistoricDay {
+    date: NaiveDate,
+    schedule: Vec<Block>,
+    mygrid_data: MygridData,
+    history_data: HistoryData,
+    weather_data: WeatherData,
+    tariffs_buy: Option<Vec<DataItem<f64>>>,
+    thresholds: Thresholds,
+    fee_source: Option<FeeSource>,
+    intervals: Vec<StoredInterval>,
+    savings: Savings,
+    schedule_summary: ScheduleSummary,
+    forecast_accuracy: DayAccuracy,
+    complete: bool,
+}
+
+impl HistoricDay {
+    /// Returns a view of the historic day
+    ///
+    /// # Arguments
+    ///
+    /// * 'time_delta' - any configured time delta
+    /// * 'version' - application version
+    fn view<'a>(&'a self, time_delta: TimeDelta, version: &'a str) -> DashView<'a> {
+        let (sold, bought, exported, imported) = energy_totals(&self.intervals);
+
+        DashView {
+            date: self.date,
+            policy: None,
+            policy_reason: None,
+            policy_timeline: None,
+            weather_data: &self.weather_data,
+            real_time_data: None,
+            tariffs_buy: self.tariffs_buy.as_ref(),
+            tariffs_buy_tomorrow: None,
+            max_tariff: max_tariff(&[self.tariffs_buy.as_ref()]),
+            thresholds: self.thresholds,
+            thresholds_tomorrow: self.thresholds,
+            fee_source: self.fee_source,
+            schedule: &self.schedule,
+            mygrid_data: &self.mygrid_data,
+            history_data: &self.history_data,
+            sold,
+            bought,
+            exported,
+            imported,
+            savings: self.savings,
+            schedule_summary: &self.schedule_summary,
+            forecast_accuracy: Some(&self.forecast_accuracy),
+            recommendations: None,
+            sources: None,
+            time_delta,
+            version,
+        }
+    }
+}
+
 impl Dispatcher {
     /// Creates a new `Dispatcher` ready for action
     ///
     /// # Arguments
     ///
+    /// * 'snapshots' - channel to publish live dash data through
+    /// * 'activity' - tracker of requests from the web server
+    /// * 'health' - health state to report to
+    /// * 'metrics' - metrics to report to
     /// * 'config' - configuration struct
-    async fn new(config: &Config) -> Result<Self> {
+    async fn new(snapshots: Snapshots, activity: Arc<Activity>, health: Arc<HealthState>, metrics: Arc<Metrics>, config: &Config) -> Result<Self> {
         let inverter = Inverter::new(&config.inverter.host).context("failed to initialize Inverter")?;
         let weather = Weather::new(&config.weather.host, &config.weather.sensor).context("failed to initialize Weather")?;
-        let nordpool = NordPool::new().context("failed to initialize NordPool")?;
-        let time_delta = if let Some(debug_run_time) = config.general.debug_run_time {
-            Utc::now() - debug_run_time.with_timezone(&Utc)
-        } else {
-            TimeDelta::seconds(0)
+        let mut nordpool = NordPool::new(
+            &config.market.area,
+            &config.market.currency,
+            get_spot_price_provider(&config.spot_price, &config.market.area, &config.market.currency).context("failed to initialize spot price provider")?,
+            get_tariff_model(&config.tariff_model),
+            &config.market.cache_path,
+        ).context("failed to initialize NordPool")?;
+        let history = HistoryStore::new(&config.history.store_path).context("failed to initialize HistoryStore")?;
+        let fee_source = select_tariff_fees(None, config.tariff_fees.as_ref())
+            .map(|(fees, source)| {
+                nordpool.set_tariff_fees(fees);
+                source
+            });
+        let notifier = Notifier::new(&config.notifications).context("failed to initialize Notifier")?;
+        let mqtt = match &config.mqtt {
+            Some(mqtt) => Some(MqttPublisher::new(mqtt, &config.market.currency, &config.general.version).context("failed to initialize MqttPublisher")?),
+            None => None,
         };
         
         Ok(Self {
             schedule: Vec::new(),
-            mygrid_data: MygridData {
-                base_cost: 0.0,
-                schedule_cost: 0.0,
-                forecast_temp: Vec::new(),
-                forecast_cloud: Vec::new(),
-                prod: Vec::new(),
-                load: Vec::new(),
-                tariff_fees: TariffFees {
-                    variable_fee: 0.0,
-                    spot_fee_percentage: 0.0,
-                    energy_tax: 0.0,
-                    swedish_power_grid: 0.0,
-                    balance_responsibility: 0.0,
-                    electric_certificate: 0.0,
-                    guarantees_of_origin: 0.0,
-                    fixed: 0.0,
-                    production_price: 0.0,
-                },
-            },
+            mygrid_data: empty_mygrid_data(),
             inverter,
             weather,
             nordpool,
+            tariff_fees: config.tariff_fees.clone(),
+            fee_source,
+            refetch_tariffs: false,
+            tomorrow_poll: DayAheadPoll::new(),
+            history,
             schedule_path: config.mygrid.schedule_path.clone(),
             base_data_path: config.mygrid.base_data_path.clone(),
+            history_date: None,
+            stored_samples: Vec::new(),
+            today_intervals: Vec::new(),
+            unpriced_intervals: Vec::new(),
             history_data: HistoryData {
                 soc_history: Vec::new(),
                 prod_history: Vec::new(),
                 load_history: Vec::new(),
             },
+            snapshots,
+            activity,
+            health,
+            metrics,
+            sources: Sources::new(),
+            notifier,
+            event_tracker: EventTracker::new(config.notifications.low_soc, config.notifications.soh_drop),
+            mqtt,
             real_time_data: RealTimeData {
                 soc: 0,
                 soh: 0,
@@ -199,237 +427,63 @@ impl Dispatcher {
             today_tariffs: None,
             tomorrow_tariffs: None,
             today_tariffs_sell: None,
+            tomorrow_tariffs_sell: None,
             policy_tariffs: HashMap::new(),
             max_tariff: 0,
             today_bought: 0.0,
             today_sold: 0.0,
             exported_energy: 0.0,
             imported_energy: 0.0,
+            savings: Savings::default(),
+            schedule_summary: ScheduleSummary::default(),
+            forecast_accuracy: None,
             usage_policy: TariffColor::Green,
-            last_request: 0,
+            policy_reason: None,
+            policy_timeline: Vec::new(),
+            policy_config: config.usage_policy.clone(),
+            power_peak: config.power_peak.clone(),
+            month_peak: None,
+            appliances: config.appliances.clone(),
             last_update: 0,
             last_policy_update: Default::default(),
-            time_delta,
+            time_delta: get_time_delta(config),
             version: config.general.version.clone(),
         })
     }
 
-    /// Executes a command and returns the same command but with requested data
+    /// Returns a view of the live data
     ///
-    /// # Arguments
-    ///
-    /// * 'cmd' - the command to evaluate and execute
-    async fn execute_cmd(&mut self, cmd: Cmd) -> Result<String> {
-        let data = match cmd {
-            Cmd::SmallDashData       => self.get_small_dash_data().context("SmallDashData generation failed")?,
-            Cmd::FullDashData        => self.get_full_dash_data().context("FullDashData generation failed")?,
-        };
-
-        Ok(data)
-    }
-    
-    /// Returns a json object with all necessary data for the small dash
-    /// 
-    fn get_small_dash_data(&self) -> Result<String> {
-        #[derive(Serialize)]
-        struct SmallDashData<'a> {
-            policy: TariffColor,
-            temp_current: f64,
-            temp_perceived: f64,
-            yesterday_min: f64,
-            yesterday_max: f64,
-            today_min: f64,
-            today_max: f64,
-            forecast_symbol: &'a Vec<DataItem<u8>>,
-            temp_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
-            tariffs_buy: Option<Series<'a, DataItem<f64>>>,
-            tariffs_buy_tomorrow: Option<Series<'a, DataItem<f64>>>,
-            max_tariff: u8,
-            schedule: &'a Vec<Block>,
-            base_cost: f64,
-            schedule_cost: f64,
-            today_sold: f64,
-            today_bought: f64,
-            today_exported: f64,
-            today_imported: f64,
-            time_delta: i64,
-            version: &'a String,
-        }
-
-        let tariffs_buy = if let Some(tariffs) = &self.today_tariffs {
-            Some(
-                Series {
-                    name: "Tariffs".to_string(),
-                    chart_type: String::new(),
-                    data: tariffs,
-                }
-            )
-        } else {
-            None
-        };
-
-        let tariffs_buy_tomorrow = if let Some(tariffs) = &self.tomorrow_tariffs {
-            Some(
-                Series {
-                    name: "Tariffs".to_string(),
-                    chart_type: String::new(),
-                    data: tariffs,
-                }
-            )
-        } else {
-            None
-        };
-        
-        let reply = SmallDashData {
-            policy: self.usage_policy.clone(),
-            temp_current: self.weather_data.temp_current,
-            temp_perceived: self.weather_data.temp_perceived,
-            yesterday_min: self.weather_data.min_max.yesterday_min,
-            yesterday_max: self.weather_data.min_max.yesterday_max,
-            today_min: self.weather_data.min_max.today_min,
-            today_max: self.weather_data.min_max.today_max,
-            forecast_symbol: &self.weather_data.forecast_symbol,
-            temp_diagram: (
-                Series {
-                    name: "Forecast".to_string(),
-                    chart_type: String::new(),
-                    data: &self.weather_data.forecast_temp,
-                },
-                Series {
-                    name: "Actual".to_string(),
-                    chart_type: String::new(),
-                    data: &self.weather_data.temp_history,
-                },
-            ),
-            tariffs_buy,
-            tariffs_buy_tomorrow,
+    fn live_view(&self) -> DashView<'_> {
+        let (_, _, today_date) = get_utc_day_start(self.utc_now(), 0);
+
+        DashView {
+            date: today_date,
+            policy: Some(self.usage_policy.clone()),
+            policy_reason: self.policy_reason.as_ref(),
+            policy_timeline: Some(&self.policy_timeline),
+            weather_data: &self.weather_data,
+            real_time_data: Some(&self.real_time_data),
+            tariffs_buy: self.today_tariffs.as_ref(),
+            tariffs_buy_tomorrow: self.tomorrow_tariffs.as_ref(),
             max_tariff: self.max_tariff,
+            thresholds: get_thresholds(&self.policy_config, self.today_tariffs.as_ref()),
+            thresholds_tomorrow: get_thresholds(&self.policy_config, self.tomorrow_tariffs.as_ref()),
+            fee_source: self.fee_source,
             schedule: &self.schedule,
-            base_cost: self.mygrid_data.base_cost,
-            schedule_cost: self.mygrid_data.schedule_cost,
-            today_sold: self.today_sold,
-            today_bought: self.today_bought,
-            today_exported: self.exported_energy,
-            today_imported: self.imported_energy,
-            time_delta: self.time_delta.num_milliseconds(),
+            mygrid_data: &self.mygrid_data,
+            history_data: &self.history_data,
+            sold: self.today_sold,
+            bought: self.today_bought,
+            exported: self.exported_energy,
+            imported: self.imported_energy,
+            savings: self.savings,
+            schedule_summary: &self.schedule_summary,
+            forecast_accuracy: self.forecast_accuracy.as_ref(),
+            recommendations: None,
+            sources: Some(&self.sources),
+            time_delta: self.time_delta,
             version: &self.version,
-        };
-
-        Ok(serde_json::to_string_pretty(&reply)?)
-    }
-
-    /// Returns a json object with all necessary data for the full dash
-    ///
-    fn get_full_dash_data(&self) -> Result<String> {
-        #[derive(Serialize)]
-        struct FullDashData<'a> {
-            policy: TariffColor,
-            temp_current: f64,
-            temp_perceived: f64,
-            yesterday_min: f64,
-            yesterday_max: f64,
-            today_min: f64,
-            today_max: f64,
-            current_prod_load: Series<'a, DataPoint<f64>>,
-            current_soc_soh: Series<'a, DataPoint<u8>>,
-            tariffs_buy: Option<Series<'a, DataItem<f64>>>,
-            max_tariff: u8,
-            prod_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
-            load_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
-            cloud_diagram: Series<'a, DataItem<f64>>,
-            temp_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
-            time_delta: i64,
-        }
-
-        let tariffs_buy = if let Some(tariffs) = &self.today_tariffs {
-            Some(
-                Series {
-                    name: "Tariffs".to_string(),
-                    chart_type: String::new(),
-                    data: tariffs,
-                }
-            )
-        } else {
-            None
-        };
-
-        let grid_description = if self.real_time_data.grid < 0.0 {
-            "Importing".to_string()
-        } else {
-            "Exporting".to_string()
-        };
-
-        let reply = FullDashData {
-            policy: self.usage_policy.clone(),
-            temp_current: self.weather_data.temp_current,
-            temp_perceived: self.weather_data.temp_perceived,
-            yesterday_min: self.weather_data.min_max.yesterday_min,
-            yesterday_max: self.weather_data.min_max.yesterday_max,
-            today_min: self.weather_data.min_max.today_min,
-            today_max: self.weather_data.min_max.today_max,
-            current_prod_load: Series {
-                name: String::new(),
-                chart_type: String::new(),
-                data: &vec![
-                    DataPoint { x: "Production".to_string(), y: self.real_time_data.prod },
-                    DataPoint { x: "Load".to_string(), y: self.real_time_data.load },
-                    DataPoint { x: grid_description, y: self.real_time_data.grid.abs() },
-                ],
-            },
-            current_soc_soh: Series {
-                name: String::new(),
-                chart_type: String::new(),
-                data: &vec![
-                    DataPoint { x: "SoC".to_string(), y: self.real_time_data.soc },
-                    DataPoint { x: "SoH".to_string(), y: self.real_time_data.soh, }
-                ],
-            },
-            tariffs_buy,
-            max_tariff: self.max_tariff,
-            prod_diagram: (
-                Series {
-                    name: "Estimated Production".to_string(),
-                    chart_type: "area".to_string(),
-                    data: &self.mygrid_data.prod,
-                },
-                Series {
-                    name: "Production".to_string(),
-                    chart_type: "line".to_string(),
-                    data: &self.history_data.prod_history,
-                },
-            ),
-            load_diagram: (
-                Series {
-                    name: "Estimated Load".to_string(),
-                    chart_type: "area".to_string(),
-                    data: &self.mygrid_data.load,
-                },
-                Series {
-                    name: "Load".to_string(),
-                    chart_type: "line".to_string(),
-                    data: &self.history_data.load_history,
-                },
-            ),
-            cloud_diagram: Series {
-                name: String::new(),
-                chart_type: String::new(),
-                data: &self.mygrid_data.forecast_cloud,
-            },
-            temp_diagram: (
-                Series {
-                    name: "Forecast (MyGrid)".to_string(),
-                    chart_type: String::new(),
-                    data: &self.mygrid_data.forecast_temp,
-                },
-                Series {
-                    name: "Actual".to_string(),
-                    chart_type: String::new(),
-                    data: &self.weather_data.temp_history,
-                },
-            ),
-            time_delta: self.time_delta.num_milliseconds(),
-        };
-        Ok(serde_json::to_string_pretty(&reply)?)
+        }
     }
 
     /// Updates weather data
@@ -470,96 +524,254 @@ impl Dispatcher {
     }
     
     /// Updates all history fields with fresh data, either delta since last update or
-    /// from midnight if old data is from yesterday
+    /// from midnight if old data is from yesterday.
+    ///
+    /// Completed samples and energy intervals are persisted in the history store, so after a
+    /// restart only what is missing since the last stored record is requested from the inverter
     ///
     /// # Arguments
     ///
     /// * 'utc_now' - 'now' according to the Utc timezone
     async fn update_history(&mut self, utc_now: DateTime<Utc>) -> Result<()> {
-        let (today_start, _today_end, _) = get_utc_day_start(utc_now, 0);
-        info!("updating SoC, pvPower and loadsPower history from inverter");
+        let (today_start, _today_end, today_date) = get_utc_day_start(utc_now, 0);
 
-        let history = self.inverter.get_history(today_start, utc_now, 5).await?;
-
-        self.history_data.soc_history = Vec::new();
-        self.history_data.prod_history = Vec::new();
-        self.history_data.load_history = Vec::new();
-
-        for sample in history.samples {
-            self.history_data.soc_history.push(DataItem{x: sample.ts, y: sample.batt_soc.round() as u8});
-            self.history_data.prod_history.push(DataItem{x: sample.ts, y: sample.production});
-            self.history_data.load_history.push(DataItem{x: sample.ts, y: sample.consumption});
-        }
-
-        if self.today_tariffs_sell.is_some() && self.today_tariffs.is_some() && utc_now.minute() % 15 == 1 {
-            let energy_intervals = self.inverter.get_energy_intervals(today_start, utc_now).await?;
-            let tariffs_sell = self.today_tariffs_sell.as_ref().unwrap();
-            let tariffs_buy = self.today_tariffs
-                .as_ref()
-                .unwrap()
-                .iter()
-                .map(|t_buy| (t_buy.x, t_buy.y))
-                .collect::<HashMap<DateTime<Utc>, f64>>();
-
-            let mut sold: f64 = 0.0;
-            let mut bought: f64 = 0.0;
-            let mut exported_energy: f64 = 0.0;
-            let mut imported_energy: f64 = 0.0;
-
-            for interval in energy_intervals.intervals {
-                let tariff_buy = *tariffs_buy.get(&interval.from_ts).unwrap_or(&0.0);
-                let tariff_sell = *tariffs_sell.get(&interval.from_ts).unwrap_or(&0.0);
-
-                sold += interval.feed_in_energy * tariff_sell;
-                bought += interval.grid_consumption_energy * tariff_buy;
-                exported_energy += interval.feed_in_energy;
-                imported_energy += interval.grid_consumption_energy;
+        if self.history_date != Some(today_date) {
+            if let Some(previous_date) = self.history_date {
+                self.finalize_history(previous_date, utc_now).await;
             }
+            self.load_history(today_date).await?;
+        }
+
+        info!("updating SoC, pvPower and loadsPower history from inverter");
+        let from = self.stored_samples.last().map_or(today_start, |s| s.ts + TimeDelta::minutes(5));
+        let history = self.inverter.get_history(from, utc_now, 5).await?;
+
+        // Only completed samples are persisted, a trailing incomplete sample is kept in memory only
+        let (completed, incomplete): (Vec<StoredSample>, Vec<StoredSample>) = to_stored_samples(history.samples)
+            .into_iter()
+            .filter(|sample| sample.ts >= from)
+            .partition(|sample| sample.ts + TimeDelta::minutes(5) <= utc_now);
+
+        self.history.append_samples(today_date, &completed).await?;
+        self.stored_samples.extend(completed);
+        self.set_history_data(&incomplete);
+
+        // Intervals are retrieved whether today's tariffs are known or not, so that power peaks are
+        // tracked, but kept in memory until they can be priced and persisted
+        let last_interval_end = self.unpriced_intervals.last().map(|i| i.to_ts)
+            .or_else(|| self.today_intervals.last().map(|i| i.to_ts))
+            .unwrap_or(today_start);
+        if utc_now - last_interval_end > TimeDelta::minutes(15) {
+            let energy_intervals = self.inverter.get_energy_intervals(last_interval_end, utc_now).await?;
+            self.unpriced_intervals.extend(energy_intervals.intervals
+                .into_iter()
+                .filter(|interval| interval.from_ts >= last_interval_end && interval.to_ts <= utc_now));
+        }
+
+        if let (Some(tariffs_sell), Some(tariffs_buy)) = (&self.today_tariffs_sell, &self.today_tariffs) &&
+            !self.unpriced_intervals.is_empty()
+        {
+            let new_intervals = to_stored_intervals(std::mem::take(&mut self.unpriced_intervals), &to_tariff_map(tariffs_buy), tariffs_sell);
+
+            self.history.append_intervals(today_date, &new_intervals).await?;
+            self.today_intervals.extend(new_intervals);
+            self.set_energy_totals();
+        }
+
+        Ok(())
+    }
+
+    /// Completes the history of a day that has ended, storing samples and intervals that weren't
+    /// retrieved before the day ended, e.g. the last interval of the day or anything missed while
+    /// the inverter was unreachable. Failures are logged, and the archive fills remaining gaps
+    /// when the day is requested
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date of the day to complete
+    /// * 'utc_now' - current time
+    async fn finalize_history(&mut self, date: NaiveDate, utc_now: DateTime<Utc>) {
+        let (_, _, today_date) = get_utc_day_start(utc_now, 0);
+        let (day_start, day_end, _) = get_utc_day_start(utc_now, (date - today_date).num_days());
+        info!("finalizing history for {}", date.format("%Y-%m-%d"));
+
+        // Today's tariffs may already have rolled over, so the day's tariffs are fetched (from cache)
+        let tariffs = log_error(self.nordpool.get_tariffs(day_start, day_end, date).await, "tariffs for finalized day")
+            .flatten();
+        let mut day = DayHistory {
+            samples: std::mem::take(&mut self.stored_samples),
+            intervals: std::mem::take(&mut self.today_intervals),
+        };
+
+        log_error(backfill_day(&self.history, &self.inverter, date, day_start, day_end, &mut day, tariffs.as_ref()).await, "finalized history");
+    }
+
+    /// Loads history for the given day from the history store, replacing whatever
+    /// history is currently held in memory
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to load history for
+    async fn load_history(&mut self, date: NaiveDate) -> Result<()> {
+        info!("loading history for {} from history store", date.format("%Y-%m-%d"));
+        let day = self.history.get_day(date).await?;
+
+        self.stored_samples = day.samples;
+        self.today_intervals = day.intervals;
+        self.unpriced_intervals.clear();
+        self.history_date = Some(date);
+        self.set_history_data(&[]);
+        self.set_energy_totals();
+        self.load_month_peak(date).await?;
+
+        Ok(())
+    }
+
+    /// Loads the power peak from the days of the month before the given day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date, days before it in the same month are loaded
+    async fn load_month_peak(&mut self, date: NaiveDate) -> Result<()> {
+        self.month_peak = None;
+        if !self.power_peak.enabled {
+            return Ok(());
+        }
 
-            self.today_sold = two_decimals(sold);
-            self.today_bought = two_decimals(bought);
-            self.exported_energy = two_decimals(exported_energy);
-            self.imported_energy = two_decimals(imported_energy);
+        info!("loading power peak for the month from history store");
+        let mut day = date.with_day(1).context("invalid date")?;
+        while day < date {
+            let intervals = self.history.get_day(day).await?.intervals;
+            self.month_peak = max_peak(self.month_peak.take(), get_peak(&intervals, &self.power_peak, DateTime::<Utc>::MAX_UTC));
+            day = day.succ_opt().context("invalid date")?;
         }
 
         Ok(())
     }
 
+    /// Sets the history data series from the stored samples and any not yet stored samples
+    ///
+    /// # Arguments
+    ///
+    /// * 'unstored' - samples not (yet) persisted in the history store
+    fn set_history_data(&mut self, unstored: &[StoredSample]) {
+        let samples = self.stored_samples.iter().chain(unstored.iter()).cloned().collect::<Vec<StoredSample>>();
+
+        self.history_data = to_history_data(&samples);
+    }
+
+    /// Returns today's energy intervals for power peak tracking, including those not yet priced
+    ///
+    fn peak_intervals(&self) -> Vec<StoredInterval> {
+        let unpriced = to_stored_intervals(self.unpriced_intervals.clone(), &HashMap::new(), &HashMap::new());
+
+        self.today_intervals.iter().cloned().chain(unpriced).collect()
+    }
+
+    /// Sets today's sold/bought and exported/imported totals and realised savings from today's
+    /// energy intervals
+    ///
+    fn set_energy_totals(&mut self) {
+        (self.today_sold, self.today_bought, self.exported_energy, self.imported_energy) = energy_totals(&self.today_intervals);
+        self.savings = get_savings(&to_export_rows(&self.stored_samples, &self.today_intervals));
+    }
+
     /// Updates with data from mygrid base data, schedule, and tariffs.
+    /// Each source is updated independently, so a failing source only leaves its own data stale
     ///
-    async fn update_mygrid_data(&mut self) -> Result<()> {
-        info!("updating MyGrid data and tariffs");
+    async fn update_mygrid_data(&mut self) {
         let utc_now = self.utc_now();
 
+        if self.sources.mygrid.is_due() {
+            let started = Instant::now();
+            let result = self.update_base_data(utc_now).await;
+            self.sources.mygrid.record(result, started.elapsed());
+        }
+
+        if self.sources.nordpool.is_due() {
+            let started = Instant::now();
+            let result = self.update_tariffs(utc_now).await;
+            self.sources.nordpool.record(result, started.elapsed());
+        }
+
+        self.max_tariff = max_tariff(&[self.today_tariffs.as_ref(), self.tomorrow_tariffs.as_ref()]);
+    }
+
+    /// Updates with data from mygrid base data and schedule
+    ///
+    /// # Arguments
+    ///
+    /// * 'utc_now' - 'now' according to the Utc timezone
+    async fn update_base_data(&mut self, utc_now: DateTime<Utc>) -> Result<()> {
+        info!("updating MyGrid data");
+        let (day_start, day_end, day_date) = get_utc_day_start(utc_now, 0);
+
         let mut schedule = get_schedule(&self.schedule_path).await?;
+        annotate_schedule(&mut schedule, &self.history_data.soc_history);
+        let rows = to_export_rows(&self.stored_samples, &self.today_intervals);
+        self.schedule_summary = track_schedule(&mut schedule, &rows, &self.history_data.soc_history, utc_now);
+        self.schedule = schedule;
+        if let Err(e) = self.history.archive_schedule(day_date, &self.schedule_path).await {
+            warn!("while archiving schedule: {:?}", e);
+        }
 
-        for block in &mut schedule {
-            let start = block.start_time;
-            let end = block.end_time;
-
-            let (current_soc, max_soc, min_soc) = self.history_data.soc_history
-                .iter()
-                .filter(|d| (start..=end).contains(&d.x))
-                .map(|d| d.y as usize)
-                .fold((None, None, None), |(_, max, min), y| {
-                    let new_max = Some(max.map_or(y, |m: usize| m.max(y)));
-                    let new_min = Some(min.map_or(y, |m: usize| m.min(y)));
-                    (Some(y), new_max, new_min)
-                });
+        let mygrid_data = find_base_data(&self.base_data_path, utc_now, day_start, day_end).await?;
+        self.set_tariff_fees(mygrid_data.as_ref().map(|data| &data.tariff_fees));
+        self.mygrid_data = mygrid_data.unwrap_or_else(empty_mygrid_data);
+        self.update_forecast_accuracy(day_date, utc_now).await?;
+
+        Ok(())
+    }
 
-            block.current_soc = current_soc;
-            block.max_soc = max_soc;
-            block.min_soc = min_soc;
+    /// Sets the tariff fees prices are calculated with, from MyGrid or the configuration.
+    /// Tariffs already retrieved are retrieved again if the source of the fees changes
+    ///
+    /// # Arguments
+    ///
+    /// * 'mygrid_fees' - fees from MyGrid base data, None if there is no base data
+    fn set_tariff_fees(&mut self, mygrid_fees: Option<&TariffFees>) {
+        if let Some((fees, source)) = select_tariff_fees(mygrid_fees, self.tariff_fees.as_ref()) {
+            if self.fee_source.is_some_and(|s| s != source) && self.today_tariffs.is_some() {
+                info!("tariff fees now from {:?}, retrieving tariffs again", source);
+                self.refetch_tariffs = true;
+            }
+            self.nordpool.set_tariff_fees(fees);
+            self.fee_source = Some(source);
         }
+    }
 
-        self.schedule = schedule;
+    /// Updates today's forecast accuracy from MyGrid's estimates and the stored samples.
+    /// The accuracy is saved to the history store whenever another hour has been completed
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - today's local date
+    /// * 'utc_now' - 'now' according to the Utc timezone
+    async fn update_forecast_accuracy(&mut self, date: NaiveDate, utc_now: DateTime<Utc>) -> Result<()> {
+        let accuracy = get_day_accuracy(date, &self.mygrid_data.prod, &self.mygrid_data.load, &self.stored_samples, utc_now);
+        let saved_hours = self.forecast_accuracy
+            .as_ref()
+            .filter(|saved| saved.date == date)
+            .map_or(0, |saved| saved.hours.len());
+
+        if accuracy.hours.len() != saved_hours {
+            self.history.save_accuracy(&accuracy).await?;
+        }
+        self.forecast_accuracy = Some(accuracy);
 
+        Ok(())
+    }
+
+    /// Updates today's and tomorrow's tariffs from Nord Pool if needed.
+    /// Tomorrow's tariffs are only polled for once expected to be published, backing off while not
+    ///
+    /// # Arguments
+    ///
+    /// * 'utc_now' - 'now' according to the Utc timezone
+    async fn update_tariffs(&mut self, utc_now: DateTime<Utc>) -> Result<()> {
         let (day_start, day_end, day_date) = get_utc_day_start(utc_now, 0);
         let (tomorrow_start, tomorrow_end, tomorrow_day_date) = get_utc_day_start(utc_now, 1);
 
-        self.mygrid_data = get_base_data(&self.base_data_path, utc_now, day_start, day_end).await?;
-        self.nordpool.set_tariff_fees(self.mygrid_data.tariff_fees.clone());
-
         self.update_tariffs_if_needed(&self.today_tariffs, day_start, day_end, day_date).await?
             .map(|tariffs| {
                 let (t_buy, t_sell) = if let Some((t_buy, t_sell)) = tariffs {
@@ -581,10 +793,27 @@ impl Dispatcher {
                     .unwrap_or_default();
             });
 
-        self.update_tariffs_if_needed(&self.tomorrow_tariffs, tomorrow_start, tomorrow_end, tomorrow_day_date).await?
-            .map(|tariffs| self.tomorrow_tariffs = tariffs.map(|tariffs| tariffs.0));
+        // Tomorrow's tariffs from yesterday are today's, they are dropped so that nothing
+        // is shown for tomorrow until its tariffs are published
+        let tomorrow_pending = clear_if_not_for_day(&mut self.tomorrow_tariffs, &mut self.tomorrow_tariffs_sell, tomorrow_start);
 
-        self.max_tariff = self.max_tariff();
+        if !tomorrow_pending || self.tomorrow_poll.is_due(tomorrow_day_date, utc_now) {
+            let result = self.update_tariffs_if_needed(&self.tomorrow_tariffs, tomorrow_start, tomorrow_end, tomorrow_day_date).await?;
+            if tomorrow_pending {
+                let published = result.as_ref().is_some_and(|t| t.is_some());
+                self.tomorrow_poll.record(tomorrow_day_date, published, utc_now);
+            }
+
+            result.map(|tariffs| {
+                let (t_buy, t_sell) = tariffs.unzip();
+
+                self.tomorrow_tariffs = t_buy;
+                self.tomorrow_tariffs_sell = t_sell.map(|t_sell| {
+                    t_sell.iter().map(|t_sell| (t_sell.x, t_sell.y)).collect::<HashMap<DateTime<Utc>, f64>>()
+                });
+            });
+        }
+        self.refetch_tariffs = false;
 
         Ok(())
     }
@@ -598,7 +827,7 @@ impl Dispatcher {
     /// * 'day_end' - end of the day to update tariffs for
     /// * 'day_date' - date of the day to update tariffs for
     async fn update_tariffs_if_needed(&self, tariffs: &Option<Vec<DataItem<f64>>>, day_start: DateTime<Utc>, day_end: DateTime<Utc>, day_date: NaiveDate) -> Result<Option<Option<(Vec<DataItem<f64>>, Vec<DataItem<f64>>)>>> {
-        let needs_tariff_update = tariffs.as_ref()
+        let needs_tariff_update = self.refetch_tariffs || tariffs.as_ref()
             .map(|t| t.first().map_or(true, |d| d.x != day_start))
             .unwrap_or(true);
 
@@ -659,7 +888,8 @@ impl Dispatcher {
         Ok(())
     }
     
-    /// Evaluates usage policy
+    /// Evaluates usage policy for the current quarter, and the policy timeline for the rest of
+    /// today and for tomorrow if tomorrow's tariffs are known
     ///
     /// # Arguments
     ///
@@ -676,69 +906,257 @@ impl Dispatcher {
             return Ok(());
         }
 
-        self.usage_policy = get_policy(
+        let mut decision = get_policy(
             current_quarter,
             self.real_time_data.soc,
             &self.schedule,
             &self.policy_tariffs,
             self.real_time_data.grid,
+            &PolicyRules::new(&self.policy_config, self.today_tariffs.as_ref()),
+        );
+        if self.power_peak.enabled {
+            let intervals = self.peak_intervals();
+            let peak = max_peak(self.month_peak.clone(), get_peak(&intervals, &self.power_peak, utc_now));
+            let status = get_peak_status(&intervals, peak, self.real_time_data.grid, &self.power_peak, utc_now);
+            decision = apply_peak_warning(decision, &status);
+        }
+        self.usage_policy = decision.color;
+        self.policy_reason = Some(decision.reason);
+
+        let (_, today_end, _) = get_utc_day_start(utc_now, 0);
+        let (_, tomorrow_end, _) = get_utc_day_start(utc_now, 1);
+        let days = [
+            (today_end, PolicyRules::new(&self.policy_config, self.today_tariffs.as_ref())),
+            (tomorrow_end, PolicyRules::new(&self.policy_config, self.tomorrow_tariffs.as_ref())),
+        ];
+        let mut tariffs = self.policy_tariffs.clone();
+        tariffs.extend(self.tomorrow_tariffs.iter().flatten().map(|t| (t.x, t.y)));
+        let load = self.mygrid_data.load.iter().map(|l| (l.x, l.y)).collect::<HashMap<DateTime<Utc>, f64>>();
+        let grid_estimates = self.mygrid_data.prod
+            .iter()
+            .map(|p| (p.x, p.y - load.get(&p.x).copied().unwrap_or(0.0)))
+            .collect::<HashMap<DateTime<Utc>, f64>>();
+
+        self.policy_timeline = get_policy_timeline(
+            current_quarter,
+            &self.schedule,
+            &tariffs,
+            &days,
+            &grid_estimates,
+            self.real_time_data.soc,
         );
 
         self.last_policy_update = utc_now;
         Ok(())
     }
     
-    /// Check if it is time to update data
+    /// Returns whether live data is polled, which it is while requested by anyone, including
+    /// connected push subscribers, or needed for notifications or MQTT
+    ///
+    fn polls_live_data(&self) -> bool {
+        self.activity.is_active() || self.snapshots.receiver_count() > 0 || self.needs_live_data()
+    }
+
+    /// Returns whether any notification to send, or MQTT publishing, depends on live data
+    /// from the inverter
+    ///
+    fn needs_live_data(&self) -> bool {
+        self.mqtt.is_some() || [EventKind::PolicyRed, EventKind::PolicyGreen, EventKind::LowSoc, EventKind::SohDrop]
+            .into_iter()
+            .any(|kind| self.notifier.is_enabled(kind))
+    }
+
+    /// Publishes live values, usage policy, tariffs and today's costs to MQTT if configured.
+    /// Nothing is published until live data has been fetched
+    ///
+    fn publish_mqtt(&self) {
+        let Some(mqtt) = &self.mqtt else { return };
+        if self.real_time_data.timestamp == 0 {
+            return;
+        }
+
+        let utc_now = self.utc_now();
+        let tariffs = self.today_tariffs.iter().chain(self.tomorrow_tariffs.iter()).flatten().collect::<Vec<&DataItem<f64>>>();
+        let tariff_now = utc_now.duration_trunc(TimeDelta::minutes(15)).ok()
+            .and_then(|quarter| tariffs.iter().find(|t| t.x == quarter))
+            .map(|t| t.y);
+        let tariff_next_hour = utc_now.duration_trunc(TimeDelta::hours(1)).ok()
+            .map(|hour| hour + TimeDelta::hours(1))
+            .map(|next| tariffs.iter().filter(|t| t.x >= next && t.x < next + TimeDelta::hours(1)).map(|t| t.y).collect::<Vec<f64>>())
+            .filter(|next| !next.is_empty())
+            .map(|next| two_decimals(next.iter().sum::<f64>() / next.len() as f64));
+
+        let state = MqttState {
+            soc: self.real_time_data.soc,
+            soh: self.real_time_data.soh,
+            prod: self.real_time_data.prod,
+            load: self.real_time_data.load,
+            grid: self.real_time_data.grid,
+            policy: self.usage_policy.clone(),
+            policy_reason: self.policy_reason.as_ref().map(|r| r.to_string()),
+            tariff_now,
+            tariff_next_hour,
+            bought: self.today_bought,
+            sold: self.today_sold,
+            net_cost: two_decimals(self.today_bought - self.today_sold),
+            saved: self.savings.saved_vs_grid_only,
+            timestamp: self.real_time_data.timestamp,
+        };
+
+        if let Err(e) = mqtt.publish(&state) {
+            error!("while publishing to MQTT: {}", e);
+        }
+    }
+
+    /// Raises notifications for events since the last check. Live data is only checked once it
+    /// has been fetched, so that defaults aren't mistaken for real values
+    ///
+    fn check_events(&mut self) {
+        let (_, _, tomorrow_date) = get_utc_day_start(self.utc_now(), 1);
+        let mut events = Vec::new();
+
+        if self.real_time_data.timestamp != 0 {
+            events.extend(self.event_tracker.soc(self.real_time_data.soc));
+            events.extend(self.event_tracker.soh(self.real_time_data.soh));
+        }
+        if self.policy_reason.is_some() {
+            events.extend(self.event_tracker.policy(&self.usage_policy, self.policy_reason.as_ref()));
+        }
+        events.extend(self.event_tracker.schedule(&self.schedule));
+        events.extend(self.event_tracker.tomorrow_tariffs(tomorrow_date, self.tomorrow_tariffs.as_ref()));
+
+        events.into_iter().for_each(|event| self.notifier.notify(event));
+    }
+
+    /// Check if it is time to update data, and if so publishes a new snapshot.
+    /// Each source is updated independently, so a failing source only leaves its own data stale
     /// 
     /// # Arguments
     /// 
-    /// * 'reset_last_request' - whether to reset or not
-    async fn check_updates(&mut self, reset_last_request: bool) -> Result<()> {
+    /// * 'force' - whether to update even if no one has requested data for a while
+    async fn check_updates(&mut self, force: bool) {
         let utc_now = self.utc_now();
         let timestamp = utc_now.timestamp();
 
-        if reset_last_request {
-            self.last_request = timestamp;
-        }
-
         if timestamp - self.last_update < 60 {
-            return Ok(())
+            return;
         }
 
-        if timestamp - self.last_request <= 1800 {
-            let _ = self.update_weather(utc_now).await?;
-            let _ = self.update_real_time_data(utc_now).await?;
-            let _ = self.update_history(utc_now).await?;
-            let _ = self.evaluate_policy(utc_now).await?;
+        if force || self.polls_live_data() {
+            if self.sources.weather.is_due() {
+                let started = Instant::now();
+                let result = self.update_weather(utc_now).await;
+                self.sources.weather.record(result, started.elapsed());
+            }
+
+            if self.sources.inverter.is_due() {
+                let started = Instant::now();
+                let result = match self.update_real_time_data(utc_now).await {
+                    Ok(_) => self.update_history(utc_now).await,
+                    Err(e) => Err(e),
+                };
+                self.sources.inverter.record(result, started.elapsed());
+            }
+
+            if let Err(e) = self.evaluate_policy(utc_now).await {
+                error!("while evaluating policy: {:?}", e);
+            }
             self.last_update = timestamp;
+            self.publish_snapshot();
         }
+    }
 
+    /// Publishes fresh live data for both dashes to handlers and push subscribers
+    ///
+    fn publish_snapshot(&self) {
+        match self.get_snapshot() {
+            Ok(snapshot) => {
+                self.snapshots.send_replace(Some(Arc::new(snapshot)));
+                self.health.sources_updated(&self.sources, self.sources.data_sources(self.polls_live_data()));
+            },
+            Err(e) => error!("while publishing snapshot: {:?}", e),
+        }
 
-        Ok(())
+        self.metrics.set_dispatcher_metrics(self.get_metrics());
     }
 
-    /// Calculates max tariff rounded up to the nearest even whole integer value, with a minimum
-    /// returned value of 4
+    /// Returns live energy and upstream source metrics in the Prometheus text format
     ///
-    fn max_tariff(&self) -> u8 {
-        let max_today = self.today_tariffs
-            .iter()
-            .flatten()
-            .map(|d| d.y.ceil() as u8)
-            .max()
-            .unwrap_or(0);
+    fn get_metrics(&self) -> String {
+        let quarter = self.utc_now().duration_trunc(TimeDelta::minutes(15)).ok();
+        let tariff_buy = quarter.and_then(|q| self.policy_tariffs.get(&q)).copied();
+        let tariff_sell = quarter.and_then(|q| self.today_tariffs_sell.as_ref()?.get(&q)).copied();
+        let policy = match self.usage_policy {
+            TariffColor::Green => 0.0,
+            TariffColor::Yellow => 1.0,
+            TariffColor::Red => 2.0,
+        };
+
+        let mut text = MetricsText::new();
+        text.metric("mygrid_dash_battery_soc_percent", "gauge", "Battery state of charge", &[("", self.real_time_data.soc as f64)]);
+        text.metric("mygrid_dash_battery_soh_percent", "gauge", "Battery state of health", &[("", self.real_time_data.soh as f64)]);
+        text.metric("mygrid_dash_power_kw", "gauge", "Current power, grid is positive when exporting", &[
+            ("kind=\"pv\"", self.real_time_data.prod),
+            ("kind=\"load\"", self.real_time_data.load),
+            ("kind=\"grid\"", self.real_time_data.grid),
+        ]);
+        text.metric("mygrid_dash_today_energy_kwh", "gauge", "Energy imported from and exported to the grid today", &[
+            ("direction=\"import\"", self.imported_energy),
+            ("direction=\"export\"", self.exported_energy),
+        ]);
+        text.metric("mygrid_dash_today_cost", "gauge", "Cost of imported and revenue from exported energy today", &[
+            ("direction=\"import\"", self.today_bought),
+            ("direction=\"export\"", self.today_sold),
+        ]);
+        if let Some(tariff_buy) = tariff_buy {
+            text.metric("mygrid_dash_tariff_buy", "gauge", "Current buy tariff", &[("", tariff_buy)]);
+        }
+        if let Some(tariff_sell) = tariff_sell {
+            text.metric("mygrid_dash_tariff_sell", "gauge", "Current sell tariff", &[("", tariff_sell)]);
+        }
+        text.metric("mygrid_dash_usage_policy", "gauge", "Usage policy, 0 = green, 1 = yellow, 2 = red", &[("", policy)]);
+        self.sources.write_metrics(&mut text);
+
+        text.into_string()
+    }
 
-        let max_tomorrow = self.tomorrow_tariffs
+    /// Returns a snapshot of the live data for both dashes
+    ///
+    fn get_snapshot(&self) -> Result<DashSnapshot> {
+        let planning = self.get_planning_data();
+        let utc_now = self.utc_now();
+        let recommendations = self.appliances
             .iter()
-            .flatten()
-            .map(|d| d.y.ceil() as u8)
-            .max()
-            .unwrap_or(0);
+            .filter_map(|appliance| recommend(appliance, &planning, utc_now).ok())
+            .collect::<Vec<Recommendation>>();
 
-        let max = max_today.max(max_tomorrow).max(4);
+        let mut view = self.live_view();
+        view.recommendations = Some(&recommendations);
 
-        // Round up to the nearest even whole integer by adding 1 and masking off the lowest bit
-        (max + 1) & !1
+        Ok(DashSnapshot {
+            small: get_small_dash_data(&view).context("SmallDashData generation failed")?,
+            full: get_full_dash_data(&view).context("FullDashData generation failed")?,
+            planning,
+        })
+    }
+
+    /// Returns today's and tomorrow's tariffs together with the schedule and production
+    /// estimates, for searching the best time to run appliances
+    ///
+    fn get_planning_data(&self) -> PlanningData {
+        let tariffs_buy = [self.today_tariffs.as_ref(), self.tomorrow_tariffs.as_ref()]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .cloned()
+            .collect::<Vec<DataItem<f64>>>();
+        let tariffs_sell = [self.today_tariffs_sell.as_ref(), self.tomorrow_tariffs_sell.as_ref()]
+            .into_iter()
+            .flatten()
+            .flat_map(|t| t.iter().map(|(k, v)| (*k, *v)))
+            .collect::<HashMap<DateTime<Utc>, f64>>();
+
+        PlanningData::new(tariffs_buy, tariffs_sell, &self.schedule, &self.mygrid_data.prod, &self.mygrid_data.load, self.time_delta)
     }
 
     /// Returns utc now with any configured time delta applied
@@ -748,6 +1166,831 @@ impl Dispatcher {
     }
 }
 
+/// Serves dash data for past days, independently of the dispatcher so that such requests
+/// neither wait for nor hold up updates of the live data
+///
+pub struct Archive {
+    inverter: Inverter,
+    weather: Weather,
+    nordpool: NordPool,
+    tariff_fees: Option<LocalTariffFees>,
+    history: HistoryStore,
+    base_data_path: String,
+    policy_config: UsagePolicy,
+    time_delta: TimeDelta,
+    version: String,
+    days: Mutex<HashMap<NaiveDate, Arc<HistoricDay>>>,
+}
+
+impl Archive {
+    /// Creates a new `Archive`
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - configuration struct
+    pub fn new(config: &Config) -> Result<Self> {
+        let inverter = Inverter::new(&config.inverter.host).context("failed to initialize Inverter")?;
+        let weather = Weather::new(&config.weather.host, &config.weather.sensor).context("failed to initialize Weather")?;
+        let nordpool = NordPool::new(
+            &config.market.area,
+            &config.market.currency,
+            get_spot_price_provider(&config.spot_price, &config.market.area, &config.market.currency).context("failed to initialize spot price provider")?,
+            get_tariff_model(&config.tariff_model),
+            &config.market.cache_path,
+        ).context("failed to initialize NordPool")?;
+        let history = HistoryStore::new(&config.history.store_path).context("failed to initialize HistoryStore")?;
+
+        Ok(Self {
+            inverter,
+            weather,
+            nordpool,
+            tariff_fees: config.tariff_fees.clone(),
+            history,
+            base_data_path: config.mygrid.base_data_path.clone(),
+            policy_config: config.usage_policy.clone(),
+            time_delta: get_time_delta(config),
+            version: config.general.version.clone(),
+            days: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns whether the given date is after today
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the requested date
+    pub fn is_future(&self, date: NaiveDate) -> bool {
+        let (_, _, today_date) = get_utc_day_start(self.utc_now(), 0);
+
+        date > today_date
+    }
+
+    /// Returns the given date if it is a day before today, otherwise None meaning that
+    /// live data is to be used. Future dates are to be rejected before calling this
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the requested date, if any
+    pub fn historic_date(&self, date: Option<NaiveDate>) -> Option<NaiveDate> {
+        let (_, _, today_date) = get_utc_day_start(self.utc_now(), 0);
+
+        date.filter(|date| *date < today_date)
+    }
+
+    /// Returns dash data for a past day. A day collected without anything missing is kept in
+    /// memory, so that upstream services aren't asked for it again
+    ///
+    /// # Arguments
+    ///
+    /// * 'dash_type' - the dash type to return data for
+    /// * 'date' - the local date to return data for
+    pub async fn get_dash_data(&self, dash_type: DashType, date: NaiveDate) -> Result<String> {
+        let cached = self.days.lock().unwrap().get(&date).cloned();
+        let day = match cached {
+            Some(day) => day,
+            None => {
+                let day = Arc::new(self.get_historic_day(date).await);
+                if day.complete {
+                    let mut days = self.days.lock().unwrap();
+                    if days.len() >= MAX_CACHED_DAYS && let Some(oldest) = days.keys().min().copied() {
+                        days.remove(&oldest);
+                    }
+                    days.insert(date, day.clone());
+                }
+                day
+            },
+        };
+        let view = day.view(self.time_delta, &self.version);
+
+        match dash_type {
+            DashType::Small => get_small_dash_data(&view).context("historic SmallDashData generation failed"),
+            DashType::Full => get_full_dash_data(&view).context("historic FullDashData generation failed"),
+        }
+    }
+
+    /// Collects MyGrid base data, tariffs, samples and energy intervals for a day from the
+    /// history store, with gaps in a past day filled from the inverter.
+    /// Data that can't be found for the day is logged and left empty
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to collect data for
+    async fn get_energy_day(&self, date: NaiveDate) -> EnergyDay {
+        let utc_now = self.utc_now();
+        let (_, _, today_date) = get_utc_day_start(utc_now, 0);
+        let (day_start, day_end, _) = get_utc_day_start(utc_now, (date - today_date).num_days());
+
+        // MyGrid base data and tariffs, using the fees that applied that day if known
+        // and otherwise the latest known fees, unless overridden by configured fees
+        let mygrid_data = log_error(find_base_data(&self.base_data_path, day_end, day_start, day_end).await, "base data")
+            .flatten();
+        let mygrid_fees = match &mygrid_data {
+            Some(data) => Some(data.tariff_fees.clone()),
+            None => log_error(find_base_data(&self.base_data_path, utc_now, day_start, day_end).await, "latest base data")
+                .flatten()
+                .map(|data| data.tariff_fees),
+        };
+        let (tariff_fees, fee_source) = select_tariff_fees(mygrid_fees.as_ref(), self.tariff_fees.as_ref())
+            .map_or_else(|| (TariffFees::default(), None), |(fees, source)| (fees, Some(source)));
+        let tariffs = log_error(self.nordpool.get_tariffs_with_fees(day_start, day_end, date, &tariff_fees).await, "tariffs")
+            .flatten();
+
+        // Samples and intervals from the history store. A past day with gaps is incomplete,
+        // e.g. from the dispatcher being down, and is filled from the inverter and saved
+        let mut stored = log_error(self.history.get_day(date).await, "stored history")
+            .unwrap_or_else(|| DayHistory { samples: Vec::new(), intervals: Vec::new() });
+
+        if date < today_date {
+            log_error(backfill_day(&self.history, &self.inverter, date, day_start, day_end, &mut stored, tariffs.as_ref()).await, "backfilled history");
+        }
+        let DayHistory { samples, intervals } = stored;
+
+        EnergyDay { mygrid_data, tariffs, fee_source, samples, intervals }
+    }
+
+    /// Collects all data for a past day from the history store, the inverter, the weather service,
+    /// MyGrid's archived base data and schedule, and Nord Pool.
+    /// Data that can't be found for the day is logged and left empty
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to collect data for
+    async fn get_historic_day(&self, date: NaiveDate) -> HistoricDay {
+        let utc_now = self.utc_now();
+        let (_, _, today_date) = get_utc_day_start(utc_now, 0);
+        let day_index = (date - today_date).num_days();
+        let (day_start, day_end, _) = get_utc_day_start(utc_now, day_index);
+        let (day_before_start, day_before_end, _) = get_utc_day_start(utc_now, day_index - 1);
+        info!("collecting historic data for {}", date.format("%Y-%m-%d"));
+
+        let EnergyDay { mygrid_data, tariffs, fee_source, samples, intervals } = self.get_energy_day(date).await;
+        let mygrid_data = mygrid_data.unwrap_or_else(empty_mygrid_data);
+        let day_history = DayHistory { samples, intervals };
+        let history_complete = !day_history.has_sample_gaps(day_start, day_end) && !day_history.has_interval_gaps(day_start, day_end);
+        let DayHistory { samples, intervals } = day_history;
+
+        let history_data = to_history_data(&samples);
+
+        // Archived schedule, only blocks touching the day are kept
+        let schedule_path = log_error(self.history.get_archived_schedule_path(date).await, "archived schedule path")
+            .flatten();
+        let mut schedule = match schedule_path {
+            Some(path) => log_error(get_schedule(&path).await, "archived schedule").unwrap_or_default(),
+            None => Vec::new(),
+        };
+        schedule.retain(|b| b.start_time < day_end && b.end_time > day_start);
+        annotate_schedule(&mut schedule, &history_data.soc_history);
+        let rows = to_export_rows(&samples, &intervals);
+        let schedule_summary = track_schedule(&mut schedule, &rows, &history_data.soc_history, day_end);
+        let forecast_accuracy = get_day_accuracy(date, &mygrid_data.prod, &mygrid_data.load, &samples, day_end);
+
+        // Weather
+        let forecast = log_error(self.weather.get_forecast(day_start, day_end).await, "weather forecast");
+        let temp_history = log_error(self.weather.get_temp_history(day_start, day_end, true).await, "temperature history");
+        let yesterday_min_max = log_error(self.weather.get_min_max(day_before_start, day_before_end).await, "min/max temperature");
+        let today_min_max = log_error(self.weather.get_min_max(day_start, day_end).await, "min/max temperature");
+        let complete = history_complete && forecast.is_some() && temp_history.is_some() &&
+            yesterday_min_max.is_some() && today_min_max.is_some();
+        let (yesterday_min, yesterday_max) = yesterday_min_max.unwrap_or((0.0, 0.0));
+        let (today_min, today_max) = today_min_max.unwrap_or((0.0, 0.0));
+
+        let (forecast_temp, forecast_symbol) = forecast
+            .map(|f| (f.forecast_temp, f.symbol_code))
+            .unwrap_or_default();
+        let (temp_history, temp_current, temp_perceived) = temp_history
+            .map(|t| (t.history, t.current_temp.unwrap_or(0.0), t.perceived_temp.unwrap_or(0.0)))
+            .unwrap_or_default();
+
+        let weather_data = WeatherData {
+            temp_history,
+            forecast_temp,
+            forecast_symbol,
+            min_max: TwoDayMinMax { yesterday_min, yesterday_max, today_min, today_max },
+            temp_current,
+            temp_perceived,
+            last_end_time: day_end,
+        };
+
+        HistoricDay {
+            date,
+            schedule,
+            mygrid_data,
+            history_data,
+            weather_data,
+            thresholds: get_thresholds(&self.policy_config, tariffs.as_ref().map(|t| &t.0)),
+            tariffs_buy: tariffs.map(|t| t.0),
+            fee_source,
+            savings: get_savings(&rows),
+            schedule_summary,
+            forecast_accuracy,
+            intervals,
+            complete,
+        }
+    }
+    
+    /// Returns one export row per 15-minute energy interval for the given day, which for today
+    /// only covers the intervals completed so far
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to export
+    pub async fn get_export(&self, date: NaiveDate) -> Vec<ExportRow> {
+        let day = self.get_energy_day(date).await;
+
+        to_export_rows(&day.samples, &day.intervals)
+    }
+
+    /// Returns energy and cost totals per period, summed up from the 15-minute intervals in
+    /// the history store
+    ///
+    /// # Arguments
+    ///
+    /// * 'period' - the period to aggregate per
+    /// * 'from' - first local date to include, defaults to a range suitable for the period
+    /// * 'to' - last local date to include, defaults to today
+    pub async fn get_aggregation(&self, period: Period, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Aggregation> {
+        let (_, _, today_date) = get_utc_day_start(self.utc_now(), 0);
+        let (default_from, default_to) = period.default_range(today_date);
+        let (from, to) = (from.unwrap_or(default_from), to.unwrap_or(default_to));
+        check_range(from, to)?;
+
+        let mut days: Vec<(NaiveDate, EnergyTotals)> = Vec::new();
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let mut day = self.history.get_day(date).await?;
+            if day.intervals.is_empty() {
+                continue;
+            }
+
+            // A partially stored past day is completed from the inverter before being counted
+            let (day_start, day_end, _) = get_utc_day_start(self.utc_now(), (date - today_date).num_days());
+            if date < today_date && (day.has_sample_gaps(day_start, day_end) || day.has_interval_gaps(day_start, day_end)) {
+                let EnergyDay { samples, intervals, .. } = self.get_energy_day(date).await;
+                day = DayHistory { samples, intervals };
+            }
+
+            let mut totals = EnergyTotals::default();
+            to_export_rows(&day.samples, &day.intervals).iter().for_each(|row| totals.add(row));
+            days.push((date, totals));
+        }
+
+        Ok(aggregate(period, from, to, &days))
+    }
+
+    /// Returns the daily forecast accuracy over a range of days, with a rolling mean of the
+    /// error. Past days that weren't completed by the dispatcher are computed from the history
+    /// store and MyGrid's archived base data, and saved as completed
+    ///
+    /// # Arguments
+    ///
+    /// * 'from' - first local date to include, defaults to a range ending today
+    /// * 'to' - last local date to include, defaults to today
+    pub async fn get_accuracy_trend(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<AccuracyTrend> {
+        let (_, _, today_date) = get_utc_day_start(self.utc_now(), 0);
+        let from = from.unwrap_or(today_date - TimeDelta::days(DEFAULT_TREND_DAYS - 1));
+        let to = to.unwrap_or(today_date);
+        check_range(from, to)?;
+
+        let mut days: Vec<DayAccuracy> = Vec::new();
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let accuracy = match self.history.get_accuracy(date).await? {
+                Some(accuracy) if accuracy.complete || date >= today_date => Some(accuracy),
+                _ if date < today_date => self.complete_accuracy(date).await?,
+                _ => None,
+            };
+            days.extend(accuracy);
+        }
+
+        Ok(AccuracyTrend { from, to, days: get_trend(&days) })
+    }
+
+    /// Computes and saves the forecast accuracy for a whole past day, or returns None if
+    /// there are no estimates or samples for the day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to compute forecast accuracy for
+    async fn complete_accuracy(&self, date: NaiveDate) -> Result<Option<DayAccuracy>> {
+        let utc_now = self.utc_now();
+        let (_, _, today_date) = get_utc_day_start(utc_now, 0);
+        let (day_start, day_end, _) = get_utc_day_start(utc_now, (date - today_date).num_days());
+
+        let Some(mygrid_data) = log_error(find_base_data(&self.base_data_path, day_end, day_start, day_end).await, "base data")
+            .flatten() else {
+            return Ok(None);
+        };
+        let mut day = self.history.get_day(date).await?;
+        if day.samples.is_empty() {
+            return Ok(None);
+        }
+        if day.has_sample_gaps(day_start, day_end) {
+            day.samples = self.get_energy_day(date).await.samples;
+        }
+
+        let mut accuracy = get_day_accuracy(date, &mygrid_data.prod, &mygrid_data.load, &day.samples, day_end);
+        accuracy.complete = true;
+        self.history.save_accuracy(&accuracy).await?;
+
+        Ok(Some(accuracy))
+    }
+
+    /// Returns utc now with any configured time delta applied
+    ///
+    fn utc_now(&self) -> DateTime<Utc> {
+        Utc::now() - self.time_delta
+    }
+}
+
+/// Returns a json object with all necessary data for the small dash
+/// 
+/// # Arguments
+///
+/// * 'view' - view of the day to return data for
+fn get_small_dash_data(view: &DashView) -> Result<String> {
+    #[derive(Serialize)]
+    struct SmallDashData<'a> {
+        date: String,
+        historic: bool,
+        policy: Option<TariffColor>,
+        policy_reason: Option<&'a PolicyReason>,
+        policy_timeline: Option<Series<'a, DataItem<PolicyDecision>>>,
+        temp_current: f64,
+        temp_perceived: f64,
+        yesterday_min: f64,
+        yesterday_max: f64,
+        today_min: f64,
+        today_max: f64,
+        forecast_symbol: &'a Vec<DataItem<u8>>,
+        temp_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
+        tariffs_buy: Option<Series<'a, DataItem<f64>>>,
+        tariffs_buy_tomorrow: Option<Series<'a, DataItem<f64>>>,
+        max_tariff: u8,
+        thresholds: Thresholds,
+        thresholds_tomorrow: Thresholds,
+        fee_source: Option<FeeSource>,
+        schedule: &'a Vec<Block>,
+        base_cost: f64,
+        schedule_cost: f64,
+        today_sold: f64,
+        today_bought: f64,
+        today_exported: f64,
+        today_imported: f64,
+        savings: Savings,
+        schedule_summary: &'a ScheduleSummary,
+        recommendations: Option<&'a Vec<Recommendation>>,
+        sources: Option<&'a Sources>,
+        time_delta: i64,
+        version: &'a str,
+    }
+
+    let tariffs_buy = if let Some(tariffs) = view.tariffs_buy {
+        Some(
+            Series {
+                name: "Tariffs".to_string(),
+                chart_type: String::new(),
+                data: tariffs,
+            }
+        )
+    } else {
+        None
+    };
+
+    let tariffs_buy_tomorrow = if let Some(tariffs) = view.tariffs_buy_tomorrow {
+        Some(
+            Series {
+                name: "Tariffs".to_string(),
+                chart_type: String::new(),
+                data: tariffs,
+            }
+        )
+    } else {
+        None
+    };
+    
+    let reply = SmallDashData {
+        date: view.date.format("%Y-%m-%d").to_string(),
+        historic: view.real_time_data.is_none(),
+        policy: view.policy.clone(),
+        policy_reason: view.policy_reason,
+        policy_timeline: view.policy_timeline.map(|data| Series {
+            name: "Policy".to_string(),
+            chart_type: String::new(),
+            data,
+        }),
+        temp_current: view.weather_data.temp_current,
+        temp_perceived: view.weather_data.temp_perceived,
+        yesterday_min: view.weather_data.min_max.yesterday_min,
+        yesterday_max: view.weather_data.min_max.yesterday_max,
+        today_min: view.weather_data.min_max.today_min,
+        today_max: view.weather_data.min_max.today_max,
+        forecast_symbol: &view.weather_data.forecast_symbol,
+        temp_diagram: (
+            Series {
+                name: "Forecast".to_string(),
+                chart_type: String::new(),
+                data: &view.weather_data.forecast_temp,
+            },
+            Series {
+                name: "Actual".to_string(),
+                chart_type: String::new(),
+                data: &view.weather_data.temp_history,
+            },
+        ),
+        tariffs_buy,
+        tariffs_buy_tomorrow,
+        max_tariff: view.max_tariff,
+        thresholds: view.thresholds,
+        thresholds_tomorrow: view.thresholds_tomorrow,
+        fee_source: view.fee_source,
+        schedule: view.schedule,
+        base_cost: view.mygrid_data.base_cost,
+        schedule_cost: view.mygrid_data.schedule_cost,
+        today_sold: view.sold,
+        today_bought: view.bought,
+        today_exported: view.exported,
+        today_imported: view.imported,
+        savings: view.savings,
+        schedule_summary: view.schedule_summary,
+        recommendations: view.recommendations,
+        sources: view.sources,
+        time_delta: view.time_delta.num_milliseconds(),
+        version: view.version,
+    };
+
+    Ok(serde_json::to_string_pretty(&reply)?)
+}
+
+/// Returns a json object with all necessary data for the full dash
+///
+/// # Arguments
+///
+/// * 'view' - view of the day to return data for
+fn get_full_dash_data(view: &DashView) -> Result<String> {
+    #[derive(Serialize)]
+    struct FullDashData<'a> {
+        date: String,
+        historic: bool,
+        policy: Option<TariffColor>,
+        policy_reason: Option<&'a PolicyReason>,
+        policy_timeline: Option<Series<'a, DataItem<PolicyDecision>>>,
+        temp_current: f64,
+        temp_perceived: f64,
+        yesterday_min: f64,
+        yesterday_max: f64,
+        today_min: f64,
+        today_max: f64,
+        current_prod_load: Option<Series<'a, DataPoint<f64>>>,
+        current_soc_soh: Option<Series<'a, DataPoint<u8>>>,
+        tariffs_buy: Option<Series<'a, DataItem<f64>>>,
+        max_tariff: u8,
+        thresholds: Thresholds,
+        fee_source: Option<FeeSource>,
+        prod_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
+        load_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
+        cloud_diagram: Series<'a, DataItem<f64>>,
+        temp_diagram: (Series<'a, DataItem<f64>>, Series<'a, DataItem<f64>>),
+        forecast_accuracy: Option<&'a DayAccuracy>,
+        sources: Option<&'a Sources>,
+        time_delta: i64,
+    }
+
+    let tariffs_buy = if let Some(tariffs) = view.tariffs_buy {
+        Some(
+            Series {
+                name: "Tariffs".to_string(),
+                chart_type: String::new(),
+                data: tariffs,
+            }
+        )
+    } else {
+        None
+    };
+
+    let current_prod_load = view.real_time_data.map(|real_time_data| {
+        let grid_description = if real_time_data.grid < 0.0 {
+            "Importing".to_string()
+        } else {
+            "Exporting".to_string()
+        };
+
+        vec![
+            DataPoint { x: "Production".to_string(), y: real_time_data.prod },
+            DataPoint { x: "Load".to_string(), y: real_time_data.load },
+            DataPoint { x: grid_description, y: real_time_data.grid.abs() },
+        ]
+    });
+
+    let current_soc_soh = view.real_time_data.map(|real_time_data| {
+        vec![
+            DataPoint { x: "SoC".to_string(), y: real_time_data.soc },
+            DataPoint { x: "SoH".to_string(), y: real_time_data.soh, }
+        ]
+    });
+
+    let reply = FullDashData {
+        date: view.date.format("%Y-%m-%d").to_string(),
+        historic: view.real_time_data.is_none(),
+        policy: view.policy.clone(),
+        policy_reason: view.policy_reason,
+        policy_timeline: view.policy_timeline.map(|data| Series {
+            name: "Policy".to_string(),
+            chart_type: String::new(),
+            data,
+        }),
+        temp_current: view.weather_data.temp_current,
+        temp_perceived: view.weather_data.temp_perceived,
+        yesterday_min: view.weather_data.min_max.yesterday_min,
+        yesterday_max: view.weather_data.min_max.yesterday_max,
+        today_min: view.weather_data.min_max.today_min,
+        today_max: view.weather_data.min_max.today_max,
+        current_prod_load: current_prod_load.as_ref().map(|data| Series {
+            name: String::new(),
+            chart_type: String::new(),
+            data,
+        }),
+        current_soc_soh: current_soc_soh.as_ref().map(|data| Series {
+            name: String::new(),
+            chart_type: String::new(),
+            data,
+        }),
+        tariffs_buy,
+        max_tariff: view.max_tariff,
+        thresholds: view.thresholds,
+        fee_source: view.fee_source,
+        prod_diagram: (
+            Series {
+                name: "Estimated Production".to_string(),
+                chart_type: "area".to_string(),
+                data: &view.mygrid_data.prod,
+            },
+            Series {
+                name: "Production".to_string(),
+                chart_type: "line".to_string(),
+                data: &view.history_data.prod_history,
+            },
+        ),
+        load_diagram: (
+            Series {
+                name: "Estimated Load".to_string(),
+                chart_type: "area".to_string(),
+                data: &view.mygrid_data.load,
+            },
+            Series {
+                name: "Load".to_string(),
+                chart_type: "line".to_string(),
+                data: &view.history_data.load_history,
+            },
+        ),
+        cloud_diagram: Series {
+            name: String::new(),
+            chart_type: String::new(),
+            data: &view.mygrid_data.forecast_cloud,
+        },
+        temp_diagram: (
+            Series {
+                name: "Forecast (MyGrid)".to_string(),
+                chart_type: String::new(),
+                data: &view.mygrid_data.forecast_temp,
+            },
+            Series {
+                name: "Actual".to_string(),
+                chart_type: String::new(),
+                data: &view.weather_data.temp_history,
+            },
+        ),
+        forecast_accuracy: view.forecast_accuracy,
+        sources: view.sources,
+        time_delta: view.time_delta.num_milliseconds(),
+    };
+    Ok(serde_json::to_string_pretty(&reply)?)
+}
+
+/// Returns the time delta to apply to 'now' when a debug run time is configured
+///
+/// # Arguments
+///
+/// * 'config' - configuration struct
+fn get_time_delta(config: &Config) -> TimeDelta {
+    if let Some(debug_run_time) = config.general.debug_run_time {
+        Utc::now() - debug_run_time.with_timezone(&Utc)
+    } else {
+        TimeDelta::seconds(0)
+    }
+}
+
+/// Calculates max tariff rounded up to the nearest even whole integer value, with a minimum
+/// returned value of 4
+///
+/// # Arguments
+///
+/// * 'tariffs' - the tariffs to find the max value in
+fn max_tariff(tariffs: &[Option<&Vec<DataItem<f64>>>]) -> u8 {
+    let max = tariffs
+        .iter()
+        .flatten()
+        .flat_map(|t| t.iter())
+        .map(|d| d.y.ceil() as u8)
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    // Round up to the nearest even whole integer by adding 1 and masking off the lowest bit
+    (max + 1) & !1
+}
+
+/// Annotates schedule blocks with current, max and min SoC from the SoC history within each block
+///
+/// # Arguments
+///
+/// * 'schedule' - the schedule blocks to annotate
+/// * 'soc_history' - SoC history to annotate from
+fn annotate_schedule(schedule: &mut [Block], soc_history: &[DataItem<u8>]) {
+    for block in schedule {
+        let start = block.start_time;
+        let end = block.end_time;
+
+        let (current_soc, max_soc, min_soc) = soc_history
+            .iter()
+            .filter(|d| (start..=end).contains(&d.x))
+            .map(|d| d.y as usize)
+            .fold((None, None, None), |(_, max, min), y| {
+                let new_max = Some(max.map_or(y, |m: usize| m.max(y)));
+                let new_min = Some(min.map_or(y, |m: usize| m.min(y)));
+                (Some(y), new_max, new_min)
+            });
+
+        block.current_soc = current_soc;
+        block.max_soc = max_soc;
+        block.min_soc = min_soc;
+    }
+}
+
+/// Transforms inverter history samples to samples as kept in the history store
+///
+/// # Arguments
+///
+/// * 'samples' - samples from the inverter
+fn to_stored_samples(samples: Vec<Samples>) -> Vec<StoredSample> {
+    samples
+        .into_iter()
+        .map(|sample| StoredSample {
+            ts: sample.ts,
+            soc: sample.batt_soc.round() as u8,
+            prod: sample.production,
+            load: sample.consumption,
+        })
+        .collect()
+}
+
+/// Transforms inverter energy intervals to intervals as kept in the history store,
+/// calculating cost and revenue per interval from the given tariffs
+///
+/// # Arguments
+///
+/// * 'intervals' - energy intervals from the inverter
+/// * 'tariffs_buy' - buy tariffs per quarter
+/// * 'tariffs_sell' - sell tariffs per quarter
+fn to_stored_intervals(intervals: Vec<EnergyIntervals>, tariffs_buy: &HashMap<DateTime<Utc>, f64>, tariffs_sell: &HashMap<DateTime<Utc>, f64>) -> Vec<StoredInterval> {
+    intervals
+        .into_iter()
+        .map(|interval| {
+            let tariff_buy = *tariffs_buy.get(&interval.from_ts).unwrap_or(&0.0);
+            let tariff_sell = *tariffs_sell.get(&interval.from_ts).unwrap_or(&0.0);
+
+            StoredInterval {
+                from_ts: interval.from_ts,
+                to_ts: interval.to_ts,
+                feed_in_energy: interval.feed_in_energy,
+                grid_consumption_energy: interval.grid_consumption_energy,
+                tariff_buy,
+                tariff_sell,
+                bought: interval.grid_consumption_energy * tariff_buy,
+                sold: interval.feed_in_energy * tariff_sell,
+            }
+        })
+        .collect()
+}
+
+/// Transforms a tariff vector to a map keyed by start time
+///
+/// # Arguments
+///
+/// * 'tariffs' - the tariffs to transform
+fn to_tariff_map(tariffs: &[DataItem<f64>]) -> HashMap<DateTime<Utc>, f64> {
+    tariffs.iter().map(|t| (t.x, t.y)).collect()
+}
+
+/// Fills gaps in the stored history of a past day from the inverter. Missing samples, and missing
+/// energy intervals if the day's tariffs are known, are appended to the history store and merged
+/// into the given day
+///
+/// # Arguments
+///
+/// * 'history' - the history store
+/// * 'inverter' - the inverter to fill gaps from
+/// * 'date' - the local date of the day
+/// * 'day_start' - start of the day
+/// * 'day_end' - end of the day (non-inclusive)
+/// * 'day' - history stored for the day, gaps are filled in place
+/// * 'tariffs' - buy and sell tariffs for the day, if known
+async fn backfill_day(history: &HistoryStore, inverter: &Inverter, date: NaiveDate, day_start: DateTime<Utc>, day_end: DateTime<Utc>, day: &mut DayHistory, tariffs: Option<&DayTariffs>) -> Result<()> {
+    if day.has_sample_gaps(day_start, day_end) {
+        info!("filling gaps in samples for {} from inverter", date.format("%Y-%m-%d"));
+        let stored = day.samples.iter().map(|s| s.ts).collect::<HashSet<DateTime<Utc>>>();
+        let missing = to_stored_samples(inverter.get_history(day_start, day_end, 5).await?.samples)
+            .into_iter()
+            .filter(|sample| sample.ts >= day_start && sample.ts + TimeDelta::minutes(5) <= day_end && !stored.contains(&sample.ts))
+            .collect::<Vec<StoredSample>>();
+
+        history.append_samples(date, &missing).await?;
+        day.samples.extend(missing);
+        day.samples.sort_by_key(|s| s.ts);
+    }
+
+    if let Some((tariffs_buy, tariffs_sell)) = tariffs && day.has_interval_gaps(day_start, day_end) {
+        info!("filling gaps in energy intervals for {} from inverter", date.format("%Y-%m-%d"));
+        let stored = day.intervals.iter().map(|i| i.from_ts).collect::<HashSet<DateTime<Utc>>>();
+        let missing = inverter.get_energy_intervals(day_start, day_end).await?.intervals
+            .into_iter()
+            .filter(|interval| interval.from_ts >= day_start && interval.to_ts <= day_end && !stored.contains(&interval.from_ts))
+            .collect::<Vec<EnergyIntervals>>();
+        let missing = to_stored_intervals(missing, &to_tariff_map(tariffs_buy), &to_tariff_map(tariffs_sell));
+
+        history.append_intervals(date, &missing).await?;
+        day.intervals.extend(missing);
+        day.intervals.sort_by_key(|i| i.from_ts);
+    }
+
+    Ok(())
+}
+
+/// Returns history data series from stored samples
+///
+/// # Arguments
+///
+/// * 'samples' - the samples to transform
+fn to_history_data(samples: &[StoredSample]) -> HistoryData {
+    let mut history_data = HistoryData {
+        soc_history: Vec::new(),
+        prod_history: Vec::new(),
+        load_history: Vec::new(),
+    };
+
+    for sample in samples {
+        history_data.soc_history.push(DataItem{x: sample.ts, y: sample.soc});
+        history_data.prod_history.push(DataItem{x: sample.ts, y: sample.prod});
+        history_data.load_history.push(DataItem{x: sample.ts, y: sample.load});
+    }
+
+    history_data
+}
+
+/// Returns sold, bought, exported energy and imported energy totals for the given intervals
+///
+/// # Arguments
+///
+/// * 'intervals' - the intervals to sum up
+fn energy_totals(intervals: &[StoredInterval]) -> (f64, f64, f64, f64) {
+    let (sold, bought, exported_energy, imported_energy) = intervals
+        .iter()
+        .fold((0.0, 0.0, 0.0, 0.0), |acc, i| {
+            (acc.0 + i.sold, acc.1 + i.bought, acc.2 + i.feed_in_energy, acc.3 + i.grid_consumption_energy)
+        });
+
+    (two_decimals(sold), two_decimals(bought), two_decimals(exported_energy), two_decimals(imported_energy))
+}
+
+/// Clears buy and sell tariffs unless they are for the day starting at the given time.
+/// Returns true if there are no tariffs for the day, i.e. they are still to be retrieved
+///
+/// # Arguments
+///
+/// * 'tariffs' - buy tariffs
+/// * 'tariffs_sell' - sell tariffs
+/// * 'day_start' - start of the day the tariffs should be for
+fn clear_if_not_for_day(tariffs: &mut Option<Vec<DataItem<f64>>>, tariffs_sell: &mut Option<HashMap<DateTime<Utc>, f64>>, day_start: DateTime<Utc>) -> bool {
+    let pending = tariffs
+        .as_ref()
+        .and_then(|t| t.first())
+        .is_none_or(|d| d.x != day_start);
+
+    if pending {
+        *tariffs = None;
+        *tariffs_sell = None;
+    }
+
+    pending
+}
+
+/// Returns the value of the given result, or None after logging the error
+///
+/// # Arguments
+///
+/// * 'result' - the result to get the value from
+/// * 'what' - description of what was requested, used in the log message
+fn log_error<T, E: Debug>(result: Result<T, E>, what: &str) -> Option<T> {
+    result.inspect_err(|e| warn!("while getting {}: {:?}", what, e)).ok()
+}
+
 /// Returns the weighted moving average from the given vector
 /// If the given vector is empty a 0.0 is returned
 /// 
@@ -802,3 +2045,51 @@ fn get_utc_day_start(date_time: DateTime<Utc>, day_index: i64) -> (DateTime<Utc>
     (start.with_timezone(&Utc), end.with_timezone(&Utc), date.date_naive())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tariffs(day_start: DateTime<Utc>) -> Vec<DataItem<f64>> {
+        (0..96)
+            .map(|q| DataItem { x: day_start + TimeDelta::minutes(15 * q), y: 1.0 })
+            .collect()
+    }
+
+    fn tariffs_sell(tariffs: &[DataItem<f64>]) -> HashMap<DateTime<Utc>, f64> {
+        tariffs.iter().map(|t| (t.x, 0.5)).collect()
+    }
+
+    #[test]
+    fn tomorrow_tariffs_kept_for_tomorrow() {
+        let tomorrow_start = "2025-10-22T22:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let buy = tariffs(tomorrow_start);
+        let (mut sell, mut buy) = (Some(tariffs_sell(&buy)), Some(buy));
+
+        assert!(!clear_if_not_for_day(&mut buy, &mut sell, tomorrow_start));
+        assert_eq!(buy.map(|b| b.len()), Some(96));
+        assert_eq!(sell.map(|s| s.len()), Some(96));
+    }
+
+    #[test]
+    fn tomorrow_tariffs_cleared_on_rollover() {
+        // Yesterday's tomorrow started at today's start, tomorrow now starts a day later
+        let today_start = "2025-10-21T22:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let tomorrow_start = today_start + TimeDelta::days(1);
+        let buy = tariffs(today_start);
+        let (mut sell, mut buy) = (Some(tariffs_sell(&buy)), Some(buy));
+
+        assert!(clear_if_not_for_day(&mut buy, &mut sell, tomorrow_start));
+        assert!(buy.is_none());
+        assert!(sell.is_none());
+    }
+
+    #[test]
+    fn missing_tariffs_pending() {
+        let tomorrow_start = "2025-10-22T22:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let (mut buy, mut sell) = (Some(Vec::new()), None);
+
+        assert!(clear_if_not_for_day(&mut buy, &mut sell, tomorrow_start));
+        assert!(buy.is_none());
+    }
+}
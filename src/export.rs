@@ -0,0 +1,85 @@
+use std::fmt::Write;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::Serialize;
+use crate::manager_history::models::{StoredInterval, StoredSample};
+
+/// One 15-minute interval as exported for reconciliation against utility invoices
+///
+#[derive(Serialize)]
+pub struct ExportRow {
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub tariff_buy: f64,
+    pub tariff_sell: f64,
+    pub imported_kwh: f64,
+    pub exported_kwh: f64,
+    pub cost: f64,
+    pub revenue: f64,
+    pub production_kwh: f64,
+    pub load_kwh: f64,
+    pub soc: Option<u8>,
+}
+
+/// Returns one export row per energy interval, with production and load summed up and the
+/// SoC taken at the end of each interval from the 5-minute samples
+///
+/// # Arguments
+///
+/// * 'samples' - the day's 5-minute samples
+/// * 'intervals' - the day's energy intervals
+pub fn to_export_rows(samples: &[StoredSample], intervals: &[StoredInterval]) -> Vec<ExportRow> {
+    let sample_hours = TimeDelta::minutes(5).num_seconds() as f64 / 3600.0;
+
+    intervals
+        .iter()
+        .map(|interval| {
+            let in_interval = samples
+                .iter()
+                .filter(|s| s.ts >= interval.from_ts && s.ts < interval.to_ts)
+                .collect::<Vec<&StoredSample>>();
+
+            ExportRow {
+                from: interval.from_ts.with_timezone(&Local),
+                to: interval.to_ts.with_timezone(&Local),
+                tariff_buy: interval.tariff_buy,
+                tariff_sell: interval.tariff_sell,
+                imported_kwh: interval.grid_consumption_energy,
+                exported_kwh: interval.feed_in_energy,
+                cost: interval.bought,
+                revenue: interval.sold,
+                production_kwh: in_interval.iter().map(|s| s.prod * sample_hours).sum(),
+                load_kwh: in_interval.iter().map(|s| s.load * sample_hours).sum(),
+                soc: in_interval.last().map(|s| s.soc),
+            }
+        })
+        .collect()
+}
+
+/// Returns the export rows as CSV with a header line
+///
+/// # Arguments
+///
+/// * 'rows' - the rows to export
+pub fn to_csv(rows: &[ExportRow]) -> String {
+    let mut csv = String::from("from,to,tariff_buy,tariff_sell,imported_kwh,exported_kwh,cost,revenue,production_kwh,load_kwh,soc\n");
+
+    for row in rows {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            row.from.to_rfc3339(),
+            row.to.to_rfc3339(),
+            row.tariff_buy,
+            row.tariff_sell,
+            row.imported_kwh,
+            row.exported_kwh,
+            row.cost,
+            row.revenue,
+            row.production_kwh,
+            row.load_kwh,
+            row.soc.map_or(String::new(), |soc| soc.to_string()),
+        );
+    }
+
+    csv
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, DurationRound, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use crate::manager_history::models::StoredSample;
+use crate::models::DataItem;
+
+/// Actual values below this (kW) are left out of the percentage error, since they would blow it up
+const MIN_MAPE_ACTUAL: f64 = 0.05;
+
+/// Number of days in the rolling mean of the trend
+const TREND_DAYS: usize = 7;
+
+/// Number of days the trend covers when no range is given
+pub const DEFAULT_TREND_DAYS: i64 = 90;
+
+/// Mean absolute error, mean absolute percentage error and bias (mean of estimate less actual)
+/// between estimated and actual values
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ErrorStats {
+    pub mae: f64,
+    pub mape: Option<f64>,
+    pub bias: f64,
+    pub samples: usize,
+}
+
+/// Forecast accuracy for one hour
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HourAccuracy {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub hour: DateTime<Utc>,
+    pub production: Option<ErrorStats>,
+    pub load: Option<ErrorStats>,
+}
+
+/// Forecast accuracy for one day, per hour and for the day as a whole. A day is complete when
+/// it has ended and the accuracy has been computed over all of it
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DayAccuracy {
+    pub date: NaiveDate,
+    pub complete: bool,
+    pub hours: Vec<HourAccuracy>,
+    pub production: Option<ErrorStats>,
+    pub load: Option<ErrorStats>,
+}
+
+/// One day in the accuracy trend together with the rolling mean of the MAE
+///
+#[derive(Serialize)]
+pub struct TrendDay {
+    pub date: NaiveDate,
+    pub production: Option<ErrorStats>,
+    pub load: Option<ErrorStats>,
+    pub production_mae_rolling: Option<f64>,
+    pub load_mae_rolling: Option<f64>,
+}
+
+/// Daily forecast accuracy over a range of days
+///
+#[derive(Serialize)]
+pub struct AccuracyTrend {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub days: Vec<TrendDay>,
+}
+
+/// Compares MyGrid's production and load estimates with the actual 5-minute samples.
+/// Each sample is compared with the estimate for its hour, and only hours that have ended are
+/// included
+///
+/// # Arguments
+///
+/// * 'date' - the local date
+/// * 'prod_estimates' - estimated production (kW)
+/// * 'load_estimates' - estimated load (kW)
+/// * 'samples' - actual samples for the day
+/// * 'until' - samples from hours ending after this are left out
+pub fn get_day_accuracy(date: NaiveDate, prod_estimates: &[DataItem<f64>], load_estimates: &[DataItem<f64>], samples: &[StoredSample], until: DateTime<Utc>) -> DayAccuracy {
+    let prod_by_hour = estimates_by_hour(prod_estimates);
+    let load_by_hour = estimates_by_hour(load_estimates);
+
+    let mut prod_pairs: BTreeMap<DateTime<Utc>, Vec<(f64, f64)>> = BTreeMap::new();
+    let mut load_pairs: BTreeMap<DateTime<Utc>, Vec<(f64, f64)>> = BTreeMap::new();
+    for sample in samples {
+        let Ok(hour) = sample.ts.duration_trunc(TimeDelta::hours(1)) else { continue };
+        if hour + TimeDelta::hours(1) > until {
+            continue;
+        }
+        if let Some(estimate) = prod_by_hour.get(&hour) {
+            prod_pairs.entry(hour).or_default().push((*estimate, sample.prod));
+        }
+        if let Some(estimate) = load_by_hour.get(&hour) {
+            load_pairs.entry(hour).or_default().push((*estimate, sample.load));
+        }
+    }
+
+    let mut hours: BTreeMap<DateTime<Utc>, HourAccuracy> = BTreeMap::new();
+    for (hour, pairs) in &prod_pairs {
+        hours.entry(*hour).or_insert_with(|| HourAccuracy { hour: *hour, production: None, load: None })
+            .production = error_stats(pairs);
+    }
+    for (hour, pairs) in &load_pairs {
+        hours.entry(*hour).or_insert_with(|| HourAccuracy { hour: *hour, production: None, load: None })
+            .load = error_stats(pairs);
+    }
+
+    DayAccuracy {
+        date,
+        complete: false,
+        hours: hours.into_values().collect(),
+        production: error_stats(&prod_pairs.into_values().flatten().collect::<Vec<(f64, f64)>>()),
+        load: error_stats(&load_pairs.into_values().flatten().collect::<Vec<(f64, f64)>>()),
+    }
+}
+
+/// Returns the daily accuracy as a trend, with a rolling mean of the MAE over the last days
+///
+/// # Arguments
+///
+/// * 'days' - day accuracies in date order
+pub fn get_trend(days: &[DayAccuracy]) -> Vec<TrendDay> {
+    let rolling = |index: usize, stats: fn(&DayAccuracy) -> Option<&ErrorStats>| {
+        let window = days[index.saturating_sub(TREND_DAYS - 1)..=index]
+            .iter()
+            .filter_map(|d| stats(d).map(|s| s.mae))
+            .collect::<Vec<f64>>();
+
+        (!window.is_empty()).then(|| round(window.iter().sum::<f64>() / window.len() as f64))
+    };
+
+    days.iter()
+        .enumerate()
+        .map(|(index, day)| TrendDay {
+            date: day.date,
+            production: day.production.clone(),
+            load: day.load.clone(),
+            production_mae_rolling: rolling(index, |d| d.production.as_ref()),
+            load_mae_rolling: rolling(index, |d| d.load.as_ref()),
+        })
+        .collect()
+}
+
+/// Returns estimates averaged per hour
+///
+/// # Arguments
+///
+/// * 'estimates' - the estimates
+fn estimates_by_hour(estimates: &[DataItem<f64>]) -> BTreeMap<DateTime<Utc>, f64> {
+    let mut by_hour: BTreeMap<DateTime<Utc>, (f64, usize)> = BTreeMap::new();
+    for estimate in estimates {
+        if let Ok(hour) = estimate.x.duration_trunc(TimeDelta::hours(1)) {
+            let entry = by_hour.entry(hour).or_insert((0.0, 0));
+            entry.0 += estimate.y;
+            entry.1 += 1;
+        }
+    }
+
+    by_hour.into_iter().map(|(hour, (sum, count))| (hour, sum / count as f64)).collect()
+}
+
+/// Returns error statistics for pairs of estimated and actual values, or None if there are none
+///
+/// # Arguments
+///
+/// * 'pairs' - estimated and actual value pairs
+fn error_stats(pairs: &[(f64, f64)]) -> Option<ErrorStats> {
+    if pairs.is_empty() {
+        return None;
+    }
+    let count = pairs.len() as f64;
+
+    let mae = pairs.iter().map(|(e, a)| (e - a).abs()).sum::<f64>() / count;
+    let bias = pairs.iter().map(|(e, a)| e - a).sum::<f64>() / count;
+    let percentages = pairs.iter()
+        .filter(|(_, a)| a.abs() >= MIN_MAPE_ACTUAL)
+        .map(|(e, a)| ((e - a) / a).abs() * 100.0)
+        .collect::<Vec<f64>>();
+    let mape = (!percentages.is_empty()).then(|| round(percentages.iter().sum::<f64>() / percentages.len() as f64));
+
+    Some(ErrorStats { mae: round(mae), mape, bias: round(bias), samples: pairs.len() })
+}
+
+/// Rounds to three decimals
+///
+/// # Arguments
+///
+/// * 'value' - the value to round
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
@@ -1,16 +1,25 @@
+use std::convert::Infallible;
 use axum::extract::{Path, Query, State};
-use axum::http::{header, HeaderName, StatusCode};
+use axum::http::{header, HeaderMap, HeaderName, StatusCode};
 use axum::response::{IntoResponse, Redirect};
+use axum::Json;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::WatchStream;
 use tracing::{error, info};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use crate::AppState;
-use crate::dispatcher::Cmd;
+use crate::dispatcher::DashType;
+use crate::aggregation::{AggregationError, Period};
+use crate::export::to_csv;
 use crate::manager_tokens::{build_access_request_url, Tokens};
+use crate::recommender::{recommend, Appliance, RecommenderError};
 
 const X_REDIRECT: HeaderName = HeaderName::from_static("x-redirect-location");
+const X_ACCEL_BUFFERING: HeaderName = HeaderName::from_static("x-accel-buffering");
 const SESSION_COOKIE: &str = "mygrid_dash";
 
 #[derive(Deserialize, Serialize)]
@@ -30,39 +39,225 @@ pub struct Context {
     context: String,
 }
 
+#[derive(Deserialize)]
+pub struct DateParam {
+    date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct RangeParams {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct RecommendParams {
+    appliance: Option<String>,
+    power: Option<f64>,
+    duration: Option<u32>,
+}
+
 //async fn get_data(data: web::Data<AppState>, path: web::Path<String>, req: HttpRequest) -> impl Responder {
-pub async fn get_data(Path(dash_type): Path<String>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
-    let cmd: Cmd;
+pub async fn get_data(Path(dash_type): Path<String>, Query(date): Query<DateParam>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let dash: DashType;
     let redirect: &str;
 
     if dash_type == "small" {
-        cmd = Cmd::SmallDashData;
+        dash = DashType::Small;
         redirect = "/login?context=/";
     } else if dash_type == "full" {
-        cmd = Cmd::FullDashData;
+        dash = DashType::Full;
         redirect = "/login?context=/full";
     } else {
         return StatusCode::BAD_REQUEST.into_response();
     }
+    if date.date.is_some_and(|date| data.archive.is_future(date)) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
     
-    if let Some(cookie) = jar.get(SESSION_COOKIE) {
-        if let Some((_, _, tokens )) = data.sessions.read().await.get(&cookie.value().to_string()) {
-            if tokens.as_ref().is_some_and(|t| !t.is_expired()) {
-                let mut comms = data.comms.lock().await;
-                comms.tx_to_mygrid.send(cmd).unwrap();
-
-                return if let Some(json) = comms.rx_from_mygrid.recv().await {
-                    ([(header::CONTENT_TYPE, "application/json")], json).into_response()
-                } else {
-                    StatusCode::NO_CONTENT.into_response()
+    if valid_session(&data, &jar).await.is_some() {
+        let json = if let Some(date) = data.archive.historic_date(date.date) {
+            match data.archive.get_dash_data(dash, date).await {
+                Ok(json) => json,
+                Err(e) => {
+                    error!("error in /data: {:?}", e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
                 }
             }
-        }
+        } else {
+            data.activity.request();
+            match data.snapshots.borrow().as_ref() {
+                Some(snapshot) => snapshot.get(dash).clone(),
+                None => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            }
+        };
+
+        return ([(header::CONTENT_TYPE, "application/json")], json).into_response();
     }
 
     ([(header::CONTENT_TYPE, "application/json"), (X_REDIRECT, redirect)], "{\"message\": \"redirect\"}").into_response()
 }
 
+/// Server-Sent Events stream pushing live dash data whenever the dispatcher has published a new snapshot.
+/// The stream ends when the session tokens expire, which makes the client fall back to
+/// a regular data request and thereby a new login if needed
+pub async fn get_events(Path(dash_type): Path<String>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let dash = match dash_type.as_str() {
+        "small" => DashType::Small,
+        "full" => DashType::Full,
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let Some(expires_at) = valid_session(&data, &jar).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let stream = WatchStream::from_changes(data.snapshots.subscribe())
+        .take_while(move |_| Utc::now() < expires_at)
+        .filter_map(move |snapshot| snapshot.map(|snapshot| {
+            Ok::<Event, Infallible>(Event::default().data(snapshot.get(dash)))
+        }));
+
+    // Tell nginx not to buffer the stream
+    ([(X_ACCEL_BUFFERING, "no")], Sse::new(stream).keep_alive(KeepAlive::default())).into_response()
+}
+
+/// Export of a day's 15-minute energy intervals, the file name is the local date followed by
+/// either '.csv' or '.json', e.g. '/export/2025-01-31.csv'
+pub async fn get_export(Path(file): Path<String>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let Some((date, extension)) = file.split_once('.') else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if data.archive.is_future(date) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    if extension != "csv" && extension != "json" {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    if valid_session(&data, &jar).await.is_none() {
+        return Redirect::temporary(&format!("/login?context=/export/{}", file)).into_response();
+    }
+
+    let rows = data.archive.get_export(date).await;
+    let (content_type, body) = if extension == "csv" {
+        ("text/csv", to_csv(&rows))
+    } else {
+        match serde_json::to_string_pretty(&rows) {
+            Ok(json) => ("application/json", json),
+            Err(e) => {
+                error!("error in /export: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    };
+    let disposition = format!("attachment; filename=\"mygrid_{}\"", file);
+
+    ([(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, disposition)], body).into_response()
+}
+
+/// Energy and cost totals per day, week, month or year, optionally for a given date range
+pub async fn get_aggregate(Path(period): Path<String>, Query(range): Query<RangeParams>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let Ok(period) = period.parse::<Period>() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if valid_session(&data, &jar).await.is_none() {
+        return ([(header::CONTENT_TYPE, "application/json"), (X_REDIRECT, "/login?context=/full")], "{\"message\": \"redirect\"}").into_response();
+    }
+
+    match data.archive.get_aggregation(period, range.from, range.to).await {
+        Ok(aggregation) => Json(aggregation).into_response(),
+        Err(e) if e.is::<AggregationError>() => StatusCode::BAD_REQUEST.into_response(),
+        Err(e) => {
+            error!("error in /aggregate: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Daily production and load forecast accuracy with a rolling trend, optionally for a given date range
+pub async fn get_accuracy(Query(range): Query<RangeParams>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    if valid_session(&data, &jar).await.is_none() {
+        return ([(header::CONTENT_TYPE, "application/json"), (X_REDIRECT, "/login?context=/full")], "{\"message\": \"redirect\"}").into_response();
+    }
+
+    match data.archive.get_accuracy_trend(range.from, range.to).await {
+        Ok(trend) => Json(trend).into_response(),
+        Err(e) if e.is::<AggregationError>() => StatusCode::BAD_REQUEST.into_response(),
+        Err(e) => {
+            error!("error in /accuracy: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Cheapest window to run an appliance in within today's and tomorrow's tariffs, either for a
+/// configured appliance, e.g. '/recommend?appliance=Dishwasher', or for a given power (kW) and
+/// duration (minutes), e.g. '/recommend?power=1.2&duration=120'
+pub async fn get_recommend(Query(params): Query<RecommendParams>, State(data): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    if valid_session(&data, &jar).await.is_none() {
+        return ([(header::CONTENT_TYPE, "application/json"), (X_REDIRECT, "/login?context=/")], "{\"message\": \"redirect\"}").into_response();
+    }
+
+    let appliance = match (params.appliance, params.power, params.duration) {
+        (Some(name), _, _) => match data.appliances.iter().find(|a| a.name.eq_ignore_ascii_case(&name)) {
+            Some(appliance) => appliance.clone(),
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+        (None, Some(power), Some(duration)) => Appliance { name: String::new(), power, duration },
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    data.activity.request();
+    let snapshot = data.snapshots.borrow().clone();
+    let Some(snapshot) = snapshot else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    match recommend(&appliance, &snapshot.planning, snapshot.planning.utc_now()) {
+        Ok(recommendation) => Json(recommendation).into_response(),
+        Err(RecommenderError::ProfileError(_)) => StatusCode::BAD_REQUEST.into_response(),
+        Err(RecommenderError::NoWindowError(_)) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Liveness endpoint, answers as long as the process is up
+pub async fn healthz(State(data): State<AppState>) -> impl IntoResponse {
+    Json(data.health.liveness(&data.version))
+}
+
+/// Readiness endpoint with status per component, answers 503 if any component isn't ready
+pub async fn readyz(State(data): State<AppState>) -> impl IntoResponse {
+    let readiness = data.health.readiness(&*data.config.read().await);
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, Json(readiness)).into_response()
+}
+
+/// Metrics endpoint in the Prometheus text format, answers 401 unless given the configured
+/// bearer token, if any
+pub async fn get_metrics(State(data): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(token) = data.metrics_token.as_ref() {
+        let bearer = headers.get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if bearer != Some(token.as_str()) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    let sessions = data.sessions.read().await
+        .values()
+        .filter(|(_, _, tokens)| tokens.is_some())
+        .count();
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], data.metrics.render(sessions)).into_response()
+}
+
 pub async fn login(State(data): State<AppState>, Query(context): Query<Context>) -> impl IntoResponse {
     let session = Uuid::new_v4().to_string();
     let state_code = Uuid::new_v4().to_string();
@@ -92,6 +287,7 @@ pub async fn code(State(data): State<AppState>, Query(params): Query<Params>, ja
                     Ok(token) => {
                         info!("{} tries to login", token.email);
                         if token.is_authorized() {
+                            data.metrics.login(true);
                             sessions.insert(state.session.clone(), (Utc::now().timestamp(), String::new(), Some(token)));
 
                             let cookie = Cookie::build((SESSION_COOKIE, state.session))
@@ -104,11 +300,13 @@ pub async fn code(State(data): State<AppState>, Query(params): Query<Params>, ja
 
                             (jar, Redirect::to(&state.context)).into_response()
                         } else {
+                            data.metrics.login(false);
                             Redirect::to("/unauthorized.html").into_response()
                         }
                     }
                     Err(e) => {
                         error!("error in /code: {}", e);
+                        data.metrics.login(false);
                         StatusCode::INTERNAL_SERVER_ERROR.into_response()
                     }
                 }
@@ -117,5 +315,21 @@ pub async fn code(State(data): State<AppState>, Query(params): Query<Params>, ja
         }
     }
 
+    data.metrics.login(false);
     Redirect::to("/unauthorized.html").into_response()
 }
+
+/// Returns the expiry time of the session tokens if the request carries a session cookie
+/// for a logged in session with valid tokens
+///
+/// # Arguments
+///
+/// * 'data' - application state holding the sessions
+/// * 'jar' - cookies from the request
+async fn valid_session(data: &AppState, jar: &CookieJar) -> Option<DateTime<Utc>> {
+    let cookie = jar.get(SESSION_COOKIE)?;
+    let sessions = data.sessions.read().await;
+    let (_, _, tokens) = sessions.get(cookie.value())?;
+
+    tokens.as_ref().filter(|t| !t.is_expired()).map(|t| t.expires_at)
+}
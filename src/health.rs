@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::initialization::Google;
+use crate::sources::DataSource;
+
+/// Max seconds between dispatch loop iterations before the dispatcher is considered stuck
+const MAX_TICK_AGE: i64 = 180;
+
+/// Health of the service as reported by the health endpoints.
+/// The dispatcher and the Google base data job report in here, while the endpoints only read
+///
+pub struct HealthState {
+    started: DateTime<Utc>,
+    max_update_age: i64,
+    dispatcher_tick: AtomicI64,
+    data_sources: Mutex<Vec<DataSource>>,
+    sources: Mutex<serde_json::Value>,
+    google: Mutex<GoogleRefresh>,
+}
+
+/// Outcome of the latest Google well known/JWKS refresh
+///
+#[derive(Default)]
+struct GoogleRefresh {
+    last_success: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Liveness {
+    status: &'static str,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    started: DateTime<Utc>,
+    version: String,
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    components: Components,
+    sources: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct Components {
+    dispatcher: ComponentStatus,
+    google: ComponentStatus,
+    data: ComponentStatus,
+}
+
+#[derive(Serialize)]
+struct ComponentStatus {
+    ready: bool,
+    #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    last_success: Option<DateTime<Utc>>,
+    message: Option<String>,
+}
+
+impl HealthState {
+    /// Returns a new instance of HealthState
+    ///
+    /// # Arguments
+    ///
+    /// * 'max_update_age' - max minutes since the last data update for the service to be ready
+    pub fn new(max_update_age: i64) -> Self {
+        Self {
+            started: Utc::now(),
+            max_update_age: max_update_age * 60,
+            dispatcher_tick: AtomicI64::new(0),
+            data_sources: Mutex::new(Vec::new()),
+            sources: Mutex::new(serde_json::Value::Null),
+            google: Mutex::new(GoogleRefresh::default()),
+        }
+    }
+
+    /// Registers that the dispatch loop is running
+    ///
+    pub fn dispatcher_tick(&self) {
+        self.dispatcher_tick.store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Registers the status of the dispatcher's upstream sources
+    ///
+    /// # Arguments
+    ///
+    /// * 'sources' - status of the dispatcher's upstream sources
+    /// * 'data_sources' - name and time of last success for the sources data readiness is based on
+    pub fn sources_updated<T: Serialize>(&self, sources: &T, data_sources: Vec<DataSource>) {
+        *self.data_sources.lock().unwrap() = data_sources;
+        if let Ok(sources) = serde_json::to_value(sources) {
+            *self.sources.lock().unwrap() = sources;
+        }
+    }
+
+    /// Registers the outcome of a Google base data refresh
+    ///
+    /// # Arguments
+    ///
+    /// * 'error' - the error if the refresh failed
+    pub fn google_refreshed(&self, error: Option<String>) {
+        let mut google = self.google.lock().unwrap();
+        match error {
+            Some(e) => google.last_error = Some(e),
+            None => {
+                google.last_success = Some(Utc::now());
+                google.last_error = None;
+            }
+        }
+    }
+
+    /// Returns liveness, which is always up as long as the process can answer
+    ///
+    /// # Arguments
+    ///
+    /// * 'version' - application version
+    pub fn liveness(&self, version: &str) -> Liveness {
+        Liveness {
+            status: "up",
+            started: self.started,
+            version: version.to_string(),
+        }
+    }
+
+    /// Returns readiness per component, the service is ready when the dispatch loop is running,
+    /// JWKS is loaded and each source data depends on has succeeded recently
+    ///
+    /// # Arguments
+    ///
+    /// * 'google_config' - google configuration data holding the JWKS
+    pub fn readiness(&self, google_config: &Google) -> Readiness {
+        let now = Utc::now().timestamp();
+
+        let tick = self.dispatcher_tick.load(Ordering::Relaxed);
+        let dispatcher = ComponentStatus {
+            ready: now - tick <= MAX_TICK_AGE,
+            last_success: DateTime::from_timestamp(tick, 0).filter(|_| tick > 0),
+            message: None,
+        };
+
+        let google_refresh = self.google.lock().unwrap();
+        let jwks_loaded = google_config.jwks.is_some() && google_config.jwks_expire > now;
+        let google = ComponentStatus {
+            ready: jwks_loaded,
+            last_success: google_refresh.last_success,
+            message: google_refresh.last_error.clone()
+                .or_else(|| (!jwks_loaded).then(|| "JWKS not loaded".to_string())),
+        };
+
+        let data_sources = self.data_sources.lock().unwrap();
+        let stale = data_sources
+            .iter()
+            .filter(|(_, last_success)| last_success.is_none_or(|l| now - l.timestamp() > self.max_update_age))
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>();
+        let data = ComponentStatus {
+            ready: !data_sources.is_empty() && stale.is_empty(),
+            last_success: data_sources.iter().map(|(_, last_success)| *last_success).min().flatten(),
+            message: (!stale.is_empty()).then(|| format!("no recent data from {}", stale.join(", "))),
+        };
+
+        Readiness {
+            ready: dispatcher.ready && google.ready && data.ready,
+            components: Components { dispatcher, google, data },
+            sources: self.sources.lock().unwrap().clone(),
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use std::{env, fs};
 use std::path::PathBuf;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Weekday};
 use jsonwebtoken::jwk::JwkSet;
 use serde::{Deserialize, Deserializer};
 use serde::de;
 use thiserror::Error;
 use tracing::level_filters::LevelFilter;
 use crate::logging::setup_logger;
+use crate::manager_notifications::models::EventKind;
+use crate::models::TariffFees;
+use crate::recommender::Appliance;
 
 #[derive(Deserialize, Clone)]
 pub struct Google {
@@ -55,6 +58,296 @@ pub struct Weather {
     pub sensor: String,
 }
 
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct History {
+    pub store_path: String,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            store_path: "history".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Market {
+    pub area: String,
+    pub currency: String,
+    pub cache_path: String,
+}
+
+impl Default for Market {
+    fn default() -> Self {
+        Self {
+            area: "SE4".to_string(),
+            currency: "SEK".to_string(),
+            cache_path: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Entsoe {
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(skip)]
+    pub token: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ElprisetJustNu {
+    pub url: String,
+}
+
+impl Default for ElprisetJustNu {
+    fn default() -> Self {
+        Self {
+            url: "https://www.elprisetjustnu.se/api/v1/prices".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SpotPriceFile {
+    pub path: String,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum SpotPriceConfig {
+    #[default]
+    NordPool,
+    Entsoe(Entsoe),
+    ElprisetJustNu(ElprisetJustNu),
+    File(SpotPriceFile),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TimeWindow {
+    pub from_hour: u32,
+    pub to_hour: u32,
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    #[serde(default)]
+    pub months: Vec<u32>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TimeOfUseFee {
+    #[serde(flatten)]
+    pub window: TimeWindow,
+    pub fee: f64,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SwedishTariff {
+    pub variable_fees: Vec<TimeOfUseFee>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct GenericTariff {
+    pub vat: f64,
+    pub fixed_markup: f64,
+    pub percentage_markup: f64,
+    pub energy_tax: f64,
+    pub grid_fee: f64,
+    pub grid_fees: Vec<TimeOfUseFee>,
+    pub sell_markup: f64,
+    pub vat_on_sell: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "model", rename_all = "lowercase")]
+pub enum TariffModelConfig {
+    Swedish(SwedishTariff),
+    Generic(GenericTariff),
+}
+
+impl Default for TariffModelConfig {
+    fn default() -> Self {
+        TariffModelConfig::Swedish(SwedishTariff::default())
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeMode {
+    #[default]
+    Fallback,
+    Override,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LocalTariffFees {
+    #[serde(default)]
+    pub mode: FeeMode,
+    #[serde(flatten)]
+    pub fees: TariffFees,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PowerPeak {
+    pub enabled: bool,
+    pub warning_ratio: f64,
+    pub min_power: f64,
+    pub windows: Vec<TimeWindow>,
+}
+
+impl Default for PowerPeak {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            warning_ratio: 0.9,
+            min_power: 2.0,
+            windows: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Health {
+    pub max_update_age: i64,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            max_update_age: 5,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MetricsEndpoint {
+    pub enabled: bool,
+    pub auth: bool,
+    #[serde(skip)]
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdMode {
+    Absolute,
+    Percentile,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct UsagePolicy {
+    pub threshold_mode: ThresholdMode,
+    pub yellow_threshold: f64,
+    pub red_threshold: f64,
+    pub yellow_percentile: f64,
+    pub red_percentile: f64,
+    pub min_soc: u8,
+    pub hold_grid_threshold: f64,
+    pub lookahead_quarters: u8,
+}
+
+impl Default for UsagePolicy {
+    fn default() -> Self {
+        Self {
+            threshold_mode: ThresholdMode::Absolute,
+            yellow_threshold: 2.0,
+            red_threshold: 4.0,
+            yellow_percentile: 50.0,
+            red_percentile: 80.0,
+            min_soc: 20,
+            hold_grid_threshold: -0.2,
+            lookahead_quarters: 4,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    None,
+    Starttls,
+    Tls,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WebhookSink {
+    pub url: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NtfySink {
+    pub url: String,
+    pub topic: String,
+    #[serde(default)]
+    pub auth: bool,
+    #[serde(skip)]
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SmtpSink {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    #[serde(skip)]
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Notifications {
+    pub events: Vec<EventKind>,
+    pub low_soc: u8,
+    pub soh_drop: u8,
+    pub webhook: Option<WebhookSink>,
+    pub ntfy: Option<NtfySink>,
+    pub smtp: Option<SmtpSink>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            events: vec![
+                EventKind::PolicyRed,
+                EventKind::PolicyGreen,
+                EventKind::LowSoc,
+                EventKind::SohDrop,
+                EventKind::BlockError,
+                EventKind::TomorrowTariffs,
+            ],
+            low_soc: 15,
+            soh_drop: 1,
+            webhook: None,
+            ntfy: None,
+            smtp: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Mqtt {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub topic_prefix: String,
+    pub discovery_prefix: String,
+    pub username: Option<String>,
+    #[serde(skip)]
+    pub password: String,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct General {
     pub debug_run_time: Option<DateTime<Local>>,
@@ -100,6 +393,28 @@ pub struct Config {
     pub inverter: Inverter,
     pub mygrid: MyGrid,
     pub weather: Weather,
+    #[serde(default, alias = "nordpool")]
+    pub market: Market,
+    #[serde(default)]
+    pub spot_price: SpotPriceConfig,
+    #[serde(default)]
+    pub tariff_model: TariffModelConfig,
+    pub tariff_fees: Option<LocalTariffFees>,
+    #[serde(default)]
+    pub power_peak: PowerPeak,
+    #[serde(default)]
+    pub history: History,
+    #[serde(default)]
+    pub health: Health,
+    #[serde(default)]
+    pub metrics: MetricsEndpoint,
+    #[serde(default)]
+    pub usage_policy: UsagePolicy,
+    #[serde(default)]
+    pub appliances: Vec<Appliance>,
+    #[serde(default)]
+    pub notifications: Notifications,
+    pub mqtt: Option<Mqtt>,
     pub general: General,
 }
 
@@ -123,6 +438,24 @@ pub fn config() -> Result<Config, ConfigError> {
         .split(',')
         .map(|s| s.trim().to_string())
         .collect::<Vec<String>>();
+    if config.market.cache_path.is_empty() {
+        config.market.cache_path = PathBuf::from(&config.history.store_path).join("nordpool").to_string_lossy().to_string();
+    }
+    if let SpotPriceConfig::Entsoe(entsoe) = &mut config.spot_price {
+        entsoe.token = read_credential("entsoe_token")?;
+    }
+    if config.metrics.auth {
+        config.metrics.token = Some(read_credential("metrics_token")?);
+    }
+    if let Some(ntfy) = config.notifications.ntfy.as_mut() && ntfy.auth {
+        ntfy.token = Some(read_credential("ntfy_token")?);
+    }
+    if let Some(smtp) = config.notifications.smtp.as_mut() && smtp.username.is_some() {
+        smtp.password = read_credential("smtp_password")?;
+    }
+    if let Some(mqtt) = config.mqtt.as_mut() && mqtt.username.is_some() {
+        mqtt.password = read_credential("mqtt_password")?;
+    }
 
     setup_logger(&config.general.log_path, config.general.log_level.0, config.general.log_to_stdout)?;
 
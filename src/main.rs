@@ -13,18 +13,20 @@ use axum::{
     Router,
 };
 use axum::body::Body;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{watch, RwLock};
 use chrono::Utc;
 use tracing::{error, info};
 use anyhow::{Context, Result};
-use tokio::sync::mpsc;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
 use crate::initialization::{config, Google};
-use crate::dispatcher::{run, Cmd};
+use crate::dispatcher::{run, Activity, Archive, Snapshots};
+use crate::health::HealthState;
+use crate::metrics::Metrics;
 use crate::handlers::*;
 use crate::manager_tokens::{google_base_data, Tokens};
+use crate::manager_notifications::send_test;
+use crate::recommender::Appliance;
 
 mod initialization;
 mod logging;
@@ -33,34 +35,58 @@ mod dispatcher;
 mod handlers;
 mod models;
 mod usage_policy;
+mod sources;
+mod health;
+mod metrics;
+mod export;
+mod aggregation;
+mod savings;
+mod schedule_tracking;
+mod forecast_accuracy;
+mod recommender;
+mod manager_notifications;
+mod manager_mqtt;
 mod manager_weather;
 mod manager_tokens;
 mod manager_nordpool;
+mod tariff_model;
+mod power_peak;
+mod manager_history;
 pub mod manager_inverter;
 
 type SessionStore = Arc<RwLock<HashMap<String, (i64, String, Option<Tokens>)>>>;
 
-struct Comms {
-    tx_to_mygrid: UnboundedSender<Cmd>,
-    rx_from_mygrid: UnboundedReceiver<String>,
-}
-
 #[derive(Clone)]
 struct AppState {
-    comms: Arc<Mutex<Comms>>,
+    snapshots: Snapshots,
+    activity: Arc<Activity>,
+    archive: Arc<Archive>,
+    health: Arc<HealthState>,
+    metrics: Arc<Metrics>,
+    version: String,
     sessions: SessionStore,
     config: Arc<RwLock<Google>>,
+    appliances: Arc<Vec<Appliance>>,
+    metrics_token: Arc<Option<String>>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Set up communication channels
-    let (mut tx_to_mygrid, mut rx_from_web) = mpsc::unbounded_channel::<Cmd>();
-    let (mut tx_to_web, mut rx_from_mygrid) = mpsc::unbounded_channel::<String>();
-    let comms = Arc::new(Mutex::new(Comms{tx_to_mygrid,rx_from_mygrid,}));
-    
+    // Set up shared state between the dispatcher and the web server
+    let snapshots: Snapshots = Arc::new(watch::Sender::new(None));
+    let activity = Arc::new(Activity::default());
+
     // Load configuration
     let config = config().context("failed to load application configuration")?;
+
+    // Only send a test notification to each configured sink if asked to
+    if std::env::args().any(|arg| arg == "--test-notifications") {
+        return send_test(&config.notifications).await.context("test notification failed");
+    }
+
+    let archive = Arc::new(Archive::new(&config).context("failed to initialize archive")?);
+    let health = Arc::new(HealthState::new(config.health.max_update_age));
+    let metrics = Arc::new(Metrics::default());
     let google_config = Arc::new(RwLock::new(config.google.clone()));
     let session_store: SessionStore = Arc::new(RwLock::new(HashMap::new()));
 
@@ -69,6 +95,7 @@ async fn main() -> Result<()> {
 
     // Enrich config
     google_base_data(google_config.clone()).await.context("initial Google base data fetch failed")?;
+    health.google_refreshed(None);
 
     // Purging of old sessions
     info!("starting sessions purge job");
@@ -76,17 +103,32 @@ async fn main() -> Result<()> {
 
     // Purging of old sessions
     info!("starting google base data update job");
-    tokio::spawn(update_google_base_data(google_config.clone()));
+    tokio::spawn(update_google_base_data(google_config.clone(), health.clone()));
 
     // Web server
     info!("starting web server");
     let static_service = ServeDir::new("static").append_index_html_on_directories(true);
-    let shared_state = AppState {comms: comms.clone(), sessions: session_store.clone(), config: google_config.clone() };
+    let shared_state = AppState {snapshots: snapshots.clone(), activity: activity.clone(), archive, health: health.clone(), metrics: metrics.clone(), version: config.general.version.clone(), sessions: session_store.clone(), config: google_config.clone(), appliances: Arc::new(config.appliances.clone()), metrics_token: Arc::new(config.metrics.token.clone()) };
+
+    // Metrics are only served when enabled, and then possibly behind a bearer token
+    let metrics_route = if config.metrics.enabled {
+        Router::new().route("/metrics", get(get_metrics))
+    } else {
+        Router::new()
+    };
 
     let app = Router::new()
         .route("/data/{dash_type}", get(get_data))
+        .route("/events/{dash_type}", get(get_events))
+        .route("/export/{file}", get(get_export))
+        .route("/aggregate/{period}", get(get_aggregate))
+        .route("/accuracy", get(get_accuracy))
+        .route("/recommend", get(get_recommend))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .route("/login", get(login))
         .route("/code", get(code))
+        .merge(metrics_route)
         .nest_service("/full", ServeFile::new("static/index_full.html"))
         .fallback_service(static_service)
         .layer(middleware::from_fn(cache_control_middleware))
@@ -106,16 +148,9 @@ async fn main() -> Result<()> {
     // Main dispatch function
     info!("starting main dispatch function");
     loop {
-        run(tx_to_web, rx_from_web, &config).await;
+        run(snapshots.clone(), activity.clone(), health.clone(), metrics.clone(), &config).await;
 
         info!("restarting main dispatch function");
-        (tx_to_mygrid, rx_from_web) = mpsc::unbounded_channel::<Cmd>();
-        (tx_to_web, rx_from_mygrid) = mpsc::unbounded_channel::<String>();
-        {
-            let mut disp_comms = comms.lock().await;
-            disp_comms.tx_to_mygrid = tx_to_mygrid;
-            disp_comms.rx_from_mygrid = rx_from_mygrid;
-        }
     }
 }
 
@@ -186,11 +221,15 @@ async fn purge_sessions(session_store: SessionStore) {
 /// # Arguments
 ///
 /// * 'google_config' - google configuration data
-async fn update_google_base_data(google_config: Arc<RwLock<Google>>) {
+/// * 'health' - health state to report the outcome to
+async fn update_google_base_data(google_config: Arc<RwLock<Google>>, health: Arc<HealthState>) {
     loop {
         tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
         if let Err(e) = google_base_data(google_config.clone()).await {
             error!("error in google_base_data: {:?}", e);
+            health.google_refreshed(Some(e.to_string()));
+        } else {
+            health.google_refreshed(None);
         }
     }
 }
@@ -0,0 +1,251 @@
+pub mod models;
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use crate::forecast_accuracy::DayAccuracy;
+use crate::manager_history::models::{DayHistory, StoredInterval, StoredSample};
+
+/// Persistent history store
+///
+/// Data is kept in append-only JSON lines files, one file per kind of data and local day,
+/// i.e. '<YYYYMMDD>_samples.jsonl' and '<YYYYMMDD>_intervals.jsonl'.
+/// Records that are appended more than once for the same timestamp are resolved on read,
+/// where the last written record wins.
+///
+/// The schedule from MyGrid is archived alongside as '<YYYYMMDD>_schedule.json', and the
+/// forecast accuracy for the day is kept in '<YYYYMMDD>_accuracy.json'.
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    /// Returns a new instance of HistoryStore, the store directory is created if needed
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - path to the directory where history files are kept
+    pub fn new(path: &str) -> Result<Self, HistoryError> {
+        std::fs::create_dir_all(path)?;
+
+        Ok(Self { path: PathBuf::from(path) })
+    }
+
+    /// Returns all stored samples and intervals for the given day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to return history for
+    pub async fn get_day(&self, date: NaiveDate) -> Result<DayHistory, HistoryError> {
+        let samples: Vec<StoredSample> = self.read(date, "samples").await?;
+        let intervals: Vec<StoredInterval> = self.read(date, "intervals").await?;
+
+        Ok(DayHistory {
+            samples: dedup_by_time(samples, |s| s.ts),
+            intervals: dedup_by_time(intervals, |i| i.from_ts),
+        })
+    }
+
+    /// Appends samples to the given day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date the samples belong to
+    /// * 'samples' - the samples to append
+    pub async fn append_samples(&self, date: NaiveDate, samples: &[StoredSample]) -> Result<(), HistoryError> {
+        self.append(date, "samples", samples).await
+    }
+
+    /// Appends energy intervals to the given day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date the intervals belong to
+    /// * 'intervals' - the intervals to append
+    pub async fn append_intervals(&self, date: NaiveDate, intervals: &[StoredInterval]) -> Result<(), HistoryError> {
+        self.append(date, "intervals", intervals).await
+    }
+
+    /// Archives the given schedule file for the given day, the archived copy is only
+    /// rewritten if the schedule has changed
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date the schedule belongs to
+    /// * 'schedule_path' - path to the schedule file to archive
+    pub async fn archive_schedule(&self, date: NaiveDate, schedule_path: &str) -> Result<(), HistoryError> {
+        let schedule = tokio::fs::read(schedule_path).await?;
+        let archive_path = self.schedule_archive_path(date);
+
+        let archived = match tokio::fs::read(&archive_path).await {
+            Ok(archived) => Some(archived),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e)?,
+        };
+
+        if archived.is_none_or(|archived| archived != schedule) {
+            tokio::fs::write(&archive_path, schedule).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path to the archived schedule for the given day, or None if no schedule
+    /// has been archived for that day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to get the archived schedule for
+    pub async fn get_archived_schedule_path(&self, date: NaiveDate) -> Result<Option<String>, HistoryError> {
+        let archive_path = self.schedule_archive_path(date);
+
+        if tokio::fs::try_exists(&archive_path).await? {
+            Ok(Some(archive_path.to_string_lossy().to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Saves the forecast accuracy for the given day, replacing any earlier saved accuracy
+    ///
+    /// # Arguments
+    ///
+    /// * 'accuracy' - the forecast accuracy to save
+    pub async fn save_accuracy(&self, accuracy: &DayAccuracy) -> Result<(), HistoryError> {
+        let json = serde_json::to_vec(accuracy)?;
+        tokio::fs::write(self.accuracy_path(accuracy.date), json).await?;
+
+        Ok(())
+    }
+
+    /// Returns the saved forecast accuracy for the given day, or None if none is saved
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to get forecast accuracy for
+    pub async fn get_accuracy(&self, date: NaiveDate) -> Result<Option<DayAccuracy>, HistoryError> {
+        match tokio::fs::read(self.accuracy_path(date)).await {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    /// Appends records as JSON lines to the file for the given day and kind
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date the records belong to
+    /// * 'kind' - kind of records, used as file name suffix
+    /// * 'records' - the records to append
+    async fn append<T: Serialize>(&self, date: NaiveDate, kind: &str, records: &[T]) -> Result<(), HistoryError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for record in records {
+            lines.push_str(&serde_json::to_string(record)?);
+            lines.push('\n');
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file_path(date, kind))
+            .await?;
+
+        file.write_all(lines.as_bytes()).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    /// Reads all records from the file for the given day and kind.
+    /// A missing file gives an empty result, and lines that can't be parsed (e.g. a partially
+    /// written last line) are skipped with a warning
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date to read records for
+    /// * 'kind' - kind of records, used as file name suffix
+    async fn read<T: DeserializeOwned>(&self, date: NaiveDate, kind: &str) -> Result<Vec<T>, HistoryError> {
+        let path = self.file_path(date, kind);
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e)?,
+        };
+
+        let records = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str::<T>(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    warn!("skipping corrupt line in {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(records)
+    }
+
+    /// Returns the path to the archived schedule for the given day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date
+    fn schedule_archive_path(&self, date: NaiveDate) -> PathBuf {
+        self.path.join(format!("{}_schedule.json", date.format("%Y%m%d")))
+    }
+
+    /// Returns the path to the saved forecast accuracy for the given day
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date
+    fn accuracy_path(&self, date: NaiveDate) -> PathBuf {
+        self.path.join(format!("{}_accuracy.json", date.format("%Y%m%d")))
+    }
+
+    /// Returns the path to the file for the given day and kind
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the local date
+    /// * 'kind' - kind of records
+    fn file_path(&self, date: NaiveDate, kind: &str) -> PathBuf {
+        self.path.join(format!("{}_{}.jsonl", date.format("%Y%m%d"), kind))
+    }
+}
+
+/// Removes duplicates by timestamp, keeping the last one written, and sorts the result
+///
+/// # Arguments
+///
+/// * 'records' - records in the order they were written
+/// * 'key' - function returning the timestamp of a record
+fn dedup_by_time<T>(records: Vec<T>, key: impl Fn(&T) -> DateTime<Utc>) -> Vec<T> {
+    records
+        .into_iter()
+        .map(|r| (key(&r), r))
+        .collect::<BTreeMap<DateTime<Utc>, T>>()
+        .into_values()
+        .collect()
+}
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
@@ -0,0 +1,174 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A 5-minute sample as stored in the history store
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StoredSample {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub ts: DateTime<Utc>,
+    pub soc: u8,
+    pub prod: f64,
+    pub load: f64,
+}
+
+/// A 15-minute energy interval including the tariffs and cost/revenue derived from them
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StoredInterval {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub from_ts: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub to_ts: DateTime<Utc>,
+    pub feed_in_energy: f64,
+    pub grid_consumption_energy: f64,
+    pub tariff_buy: f64,
+    pub tariff_sell: f64,
+    pub bought: f64,
+    pub sold: f64,
+}
+
+/// Length of a sample
+const SAMPLE_LENGTH: TimeDelta = TimeDelta::minutes(5);
+
+/// Slack allowed between consecutive records, since the inverter's timestamps may drift somewhat
+const GAP_TOLERANCE: TimeDelta = TimeDelta::minutes(1);
+
+/// Everything stored for one day
+pub struct DayHistory {
+    pub samples: Vec<StoredSample>,
+    pub intervals: Vec<StoredInterval>,
+}
+
+impl DayHistory {
+    /// Returns whether samples are missing anywhere in the day, each sample covering the
+    /// five minutes from its timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * 'day_start' - start of the day
+    /// * 'day_end' - end of the day (non-inclusive)
+    pub fn has_sample_gaps(&self, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> bool {
+        has_gaps(self.samples.iter().map(|s| (s.ts, s.ts + SAMPLE_LENGTH)), day_start, day_end)
+    }
+
+    /// Returns whether energy intervals are missing anywhere in the day
+    ///
+    /// # Arguments
+    ///
+    /// * 'day_start' - start of the day
+    /// * 'day_end' - end of the day (non-inclusive)
+    pub fn has_interval_gaps(&self, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> bool {
+        has_gaps(self.intervals.iter().map(|i| (i.from_ts, i.to_ts)), day_start, day_end)
+    }
+}
+
+/// Returns whether the given spans leave any part of the day uncovered
+///
+/// # Arguments
+///
+/// * 'spans' - start and end (non-inclusive) of each span
+/// * 'day_start' - start of the day
+/// * 'day_end' - end of the day (non-inclusive)
+fn has_gaps(spans: impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)>, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> bool {
+    let mut spans = spans.collect::<Vec<_>>();
+    spans.sort_by_key(|(from, _)| *from);
+
+    let mut covered = day_start;
+    for (from, to) in spans {
+        if from > covered + GAP_TOLERANCE {
+            return true;
+        }
+        covered = covered.max(to);
+    }
+
+    covered + GAP_TOLERANCE < day_end
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    fn day() -> (DateTime<Utc>, DateTime<Utc>) {
+        let day_start = Utc.with_ymd_and_hms(2025, 10, 21, 22, 0, 0).unwrap();
+        (day_start, day_start + TimeDelta::days(1))
+    }
+
+    fn samples(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<StoredSample> {
+        (0..(to - from).num_minutes() / 5)
+            .map(|i| StoredSample { ts: from + TimeDelta::minutes(5 * i), soc: 50, prod: 0.0, load: 0.0 })
+            .collect()
+    }
+
+    fn intervals(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<StoredInterval> {
+        (0..(to - from).num_minutes() / 15)
+            .map(|i| StoredInterval {
+                from_ts: from + TimeDelta::minutes(15 * i),
+                to_ts: from + TimeDelta::minutes(15 * (i + 1)),
+                feed_in_energy: 0.0,
+                grid_consumption_energy: 0.0,
+                tariff_buy: 0.0,
+                tariff_sell: 0.0,
+                bought: 0.0,
+                sold: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn complete_day_has_no_gaps() {
+        let (day_start, day_end) = day();
+        let day = DayHistory { samples: samples(day_start, day_end), intervals: intervals(day_start, day_end) };
+
+        assert!(!day.has_sample_gaps(day_start, day_end));
+        assert!(!day.has_interval_gaps(day_start, day_end));
+    }
+
+    #[test]
+    fn empty_day_has_gaps() {
+        let (day_start, day_end) = day();
+        let day = DayHistory { samples: Vec::new(), intervals: Vec::new() };
+
+        assert!(day.has_sample_gaps(day_start, day_end));
+        assert!(day.has_interval_gaps(day_start, day_end));
+    }
+
+    #[test]
+    fn day_cut_short_has_gaps() {
+        let (day_start, day_end) = day();
+        let cut = day_end - TimeDelta::minutes(15);
+        let day = DayHistory { samples: samples(day_start, cut), intervals: intervals(day_start, cut) };
+
+        assert!(day.has_sample_gaps(day_start, day_end));
+        assert!(day.has_interval_gaps(day_start, day_end));
+    }
+
+    #[test]
+    fn day_starting_late_has_gaps() {
+        let (day_start, day_end) = day();
+        let late = day_start + TimeDelta::minutes(15);
+        let day = DayHistory { samples: samples(late, day_end), intervals: intervals(late, day_end) };
+
+        assert!(day.has_sample_gaps(day_start, day_end));
+        assert!(day.has_interval_gaps(day_start, day_end));
+    }
+
+    #[test]
+    fn missing_records_within_day_are_gaps() {
+        let (day_start, day_end) = day();
+        let mut day = DayHistory { samples: samples(day_start, day_end), intervals: intervals(day_start, day_end) };
+        day.samples.remove(100);
+        day.intervals.remove(40);
+
+        assert!(day.has_sample_gaps(day_start, day_end));
+        assert!(day.has_interval_gaps(day_start, day_end));
+    }
+
+    #[test]
+    fn drifting_samples_are_not_gaps() {
+        let (day_start, day_end) = day();
+        let mut day = DayHistory { samples: samples(day_start, day_end), intervals: Vec::new() };
+        day.samples.iter_mut().skip(1).step_by(2).for_each(|s| s.ts += TimeDelta::seconds(30));
+
+        assert!(!day.has_sample_gaps(day_start, day_end));
+    }
+}
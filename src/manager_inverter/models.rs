@@ -20,7 +20,7 @@ pub struct HistoryRecord {
     pub samples: Vec<Samples>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct EnergyIntervals {
     #[serde(with = "chrono::serde::ts_seconds")]
     pub from_ts: DateTime<Utc>,
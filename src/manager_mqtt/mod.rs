@@ -0,0 +1,168 @@
+pub mod models;
+
+use std::time::Duration;
+use rumqttc::{AsyncClient, ClientError, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use thiserror::Error;
+use tracing::{error, info};
+use crate::initialization::Mqtt;
+use crate::manager_mqtt::models::{Device, DiscoveryConfig, Entity, MqttState, Unit};
+
+/// Seconds to wait before reconnecting after a lost or failed connection
+const RECONNECT_DELAY: u64 = 10;
+
+/// Entities published through Home Assistant discovery, keyed by their field in the state
+const ENTITIES: [Entity; 12] = [
+    Entity { key: "soc", name: "SoC", unit: Unit::Percent, device_class: Some("battery"), state_class: Some("measurement") },
+    Entity { key: "soh", name: "SoH", unit: Unit::Percent, device_class: None, state_class: Some("measurement") },
+    Entity { key: "prod", name: "Production", unit: Unit::Power, device_class: Some("power"), state_class: Some("measurement") },
+    Entity { key: "load", name: "Load", unit: Unit::Power, device_class: Some("power"), state_class: Some("measurement") },
+    Entity { key: "grid", name: "Grid", unit: Unit::Power, device_class: Some("power"), state_class: Some("measurement") },
+    Entity { key: "policy", name: "Usage policy", unit: Unit::None, device_class: Some("enum"), state_class: None },
+    Entity { key: "tariff_now", name: "Tariff now", unit: Unit::Tariff, device_class: None, state_class: Some("measurement") },
+    Entity { key: "tariff_next_hour", name: "Tariff next hour", unit: Unit::Tariff, device_class: None, state_class: Some("measurement") },
+    Entity { key: "bought", name: "Bought today", unit: Unit::Currency, device_class: Some("monetary"), state_class: Some("total") },
+    Entity { key: "sold", name: "Sold today", unit: Unit::Currency, device_class: Some("monetary"), state_class: Some("total") },
+    Entity { key: "net_cost", name: "Net cost today", unit: Unit::Currency, device_class: Some("monetary"), state_class: Some("total") },
+    Entity { key: "saved", name: "Saved today", unit: Unit::Currency, device_class: Some("monetary"), state_class: Some("total") },
+];
+
+/// MQTT publisher for Home Assistant
+///
+/// The connection is driven by a background task that reconnects as needed, and that
+/// publishes discovery config and availability each time a connection is established
+pub struct MqttPublisher {
+    client: AsyncClient,
+    state_topic: String,
+}
+
+impl MqttPublisher {
+    /// Returns a new instance of MqttPublisher and starts connecting to the broker
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - MQTT configuration
+    /// * 'currency' - currency tariffs and costs are given in
+    /// * 'version' - application version, shown on the device in Home Assistant
+    pub fn new(config: &Mqtt, currency: &str, version: &str) -> Result<Self, MqttError> {
+        let state_topic = format!("{}/state", config.topic_prefix);
+        let availability_topic = format!("{}/availability", config.topic_prefix);
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options
+            .set_keep_alive(Duration::from_secs(30))
+            .set_last_will(LastWill::new(&availability_topic, "offline", QoS::AtLeastOnce, true));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, &config.password);
+        }
+
+        let discovery = get_discovery(config, &state_topic, &availability_topic, currency, version)?;
+        let (client, event_loop) = AsyncClient::new(options, discovery.len() + 10);
+        tokio::spawn(drive_connection(client.clone(), event_loop, discovery, availability_topic));
+
+        Ok(Self { client, state_topic })
+    }
+
+    /// Publishes the current state, retained so that Home Assistant has it also after a restart.
+    /// Nothing is queued while the broker can't keep up, since the next state replaces it anyway
+    ///
+    /// # Arguments
+    ///
+    /// * 'state' - the state to publish
+    pub fn publish(&self, state: &MqttState) -> Result<(), MqttError> {
+        let payload = serde_json::to_string(state)?;
+        self.client.try_publish(&self.state_topic, QoS::AtMostOnce, true, payload)?;
+
+        Ok(())
+    }
+}
+
+/// Polls the connection, publishing discovery config and availability on each new connection.
+/// Failed connections are retried after a delay
+///
+/// # Arguments
+///
+/// * 'client' - client to publish with
+/// * 'event_loop' - the connection's event loop
+/// * 'discovery' - discovery topics and payloads
+/// * 'availability_topic' - topic to publish availability to
+async fn drive_connection(client: AsyncClient, mut event_loop: EventLoop, discovery: Vec<(String, String)>, availability_topic: String) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("connected to MQTT broker");
+                let result = discovery
+                    .iter()
+                    .try_for_each(|(topic, payload)| client.try_publish(topic, QoS::AtLeastOnce, true, payload.as_str()))
+                    .and_then(|_| client.try_publish(&availability_topic, QoS::AtLeastOnce, true, "online"));
+                if let Err(e) = result {
+                    error!("while publishing MQTT discovery: {}", e);
+                }
+            },
+            Ok(_) => {},
+            Err(e) => {
+                error!("MQTT connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY)).await;
+            },
+        }
+    }
+}
+
+/// Returns discovery topic and payload for each entity
+///
+/// # Arguments
+///
+/// * 'config' - MQTT configuration
+/// * 'state_topic' - topic the state is published to
+/// * 'availability_topic' - topic availability is published to
+/// * 'currency' - currency tariffs and costs are given in
+/// * 'version' - application version
+fn get_discovery(config: &Mqtt, state_topic: &str, availability_topic: &str, currency: &str, version: &str) -> Result<Vec<(String, String)>, MqttError> {
+    let device = Device {
+        identifiers: vec![config.client_id.clone()],
+        name: "MyGrid Dash".to_string(),
+        manufacturer: "gostonefire".to_string(),
+        model: "mygrid_dash".to_string(),
+        sw_version: version.to_string(),
+    };
+
+    ENTITIES
+        .iter()
+        .map(|entity| {
+            let unit = match entity.unit {
+                Unit::None => None,
+                Unit::Percent => Some("%".to_string()),
+                Unit::Power => Some("kW".to_string()),
+                Unit::Tariff => Some(format!("{}/kWh", currency)),
+                Unit::Currency => Some(currency.to_string()),
+            };
+            let is_policy = entity.key == "policy";
+
+            let discovery = DiscoveryConfig {
+                name: entity.name,
+                unique_id: format!("{}_{}", config.client_id, entity.key),
+                default_entity_id: format!("sensor.{}_{}", config.client_id, entity.key),
+                state_topic,
+                value_template: format!("{{{{ value_json.{} }}}}", entity.key),
+                availability_topic,
+                unit_of_measurement: unit,
+                device_class: entity.device_class,
+                state_class: entity.state_class,
+                options: is_policy.then(|| vec!["Green", "Yellow", "Red"]),
+                json_attributes_topic: is_policy.then_some(state_topic),
+                json_attributes_template: is_policy.then_some("{{ {'reason': value_json.policy_reason} | tojson }}"),
+                device: &device,
+            };
+
+            let topic = format!("{}/sensor/{}/{}/config", config.discovery_prefix, config.client_id, entity.key);
+            Ok((topic, serde_json::to_string(&discovery)?))
+        })
+        .collect()
+}
+
+#[derive(Debug, Error)]
+pub enum MqttError {
+    #[error("SerdeJsonError: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error("ClientError: {0}")]
+    ClientError(#[from] ClientError),
+}
@@ -0,0 +1,73 @@
+use serde::Serialize;
+use crate::models::TariffColor;
+
+/// Values published to the state topic, each entity picks its value out of it
+#[derive(Serialize)]
+pub struct MqttState {
+    pub soc: u8,
+    pub soh: u8,
+    pub prod: f64,
+    pub load: f64,
+    pub grid: f64,
+    pub policy: TariffColor,
+    pub policy_reason: Option<String>,
+    pub tariff_now: Option<f64>,
+    pub tariff_next_hour: Option<f64>,
+    pub bought: f64,
+    pub sold: f64,
+    pub net_cost: f64,
+    pub saved: f64,
+    pub timestamp: i64,
+}
+
+/// Home Assistant MQTT discovery config for one entity
+#[derive(Serialize)]
+pub struct DiscoveryConfig<'a> {
+    pub name: &'a str,
+    pub unique_id: String,
+    pub default_entity_id: String,
+    pub state_topic: &'a str,
+    pub value_template: String,
+    pub availability_topic: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_of_measurement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_class: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_class: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_attributes_topic: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_attributes_template: Option<&'a str>,
+    pub device: &'a Device,
+}
+
+/// The device all entities belong to in Home Assistant
+#[derive(Serialize)]
+pub struct Device {
+    pub identifiers: Vec<String>,
+    pub name: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub sw_version: String,
+}
+
+/// Unit an entity is measured in
+pub enum Unit {
+    None,
+    Percent,
+    Power,
+    Tariff,
+    Currency,
+}
+
+/// An entity as published through discovery
+pub struct Entity {
+    pub key: &'static str,
+    pub name: &'static str,
+    pub unit: Unit,
+    pub device_class: Option<&'static str>,
+    pub state_class: Option<&'static str>,
+}
@@ -24,39 +24,19 @@ pub async fn get_schedule(schedule_path: &str) -> Result<Vec<Block>, MyGridError
     Ok(blocks)
 }
 
-/// Reads base data from mygrid and returns a `BaseData` struct
-/// 
+/// Reads base data from mygrid and returns a `BaseData` struct, or None if no base data file is found
+///
 /// # Arguments
-/// 
+///
 /// * 'base_data_path' - full path to the base data file from mygrid
-/// * 'utc_now' - date time to check a valid base data file for
+/// * 'target_time' - date time to check a valid base data file for
 /// * 'day_start' - start of day to filter for
 /// * 'day_end' - end of day to filter for (non-inclusive)
-pub async fn get_base_data(base_data_path: &str, utc_now: DateTime<Utc>, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> Result<MygridData, MyGridError> {
-
-    let mut mygrid = MygridData {
-        base_cost: 0.0,
-        schedule_cost: 0.0,
-        forecast_temp: Vec::new(),
-        forecast_cloud: Vec::new(),
-        prod: Vec::new(),
-        load: Vec::new(),
-        tariff_fees: TariffFees {
-            variable_fee: 0.0,
-            spot_fee_percentage: 0.0,
-            energy_tax: 0.0,
-            swedish_power_grid: 0.0,
-            balance_responsibility: 0.0,
-            electric_certificate: 0.0,
-            guarantees_of_origin: 0.0,
-            fixed: 0.0,
-            production_price: 0.0,
-        },
-    };
-
-    let json = get_latest_base_data_content(base_data_path, utc_now).await?;
+pub async fn find_base_data(base_data_path: &str, target_time: DateTime<Utc>, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> Result<Option<MygridData>, MyGridError> {
+    let json = get_latest_base_data_content(base_data_path, target_time).await?;
 
     if let Some(json) = json {
+        let mut mygrid = empty_mygrid_data();
         let base_data: BaseData = serde_json::from_str(&json)?;
         mygrid.base_cost = base_data.base_cost;
         mygrid.schedule_cost = base_data.schedule_cost;
@@ -82,9 +62,25 @@ pub async fn get_base_data(base_data_path: &str, utc_now: DateTime<Utc>, day_sta
         base_data.consumption.into_iter().filter(|d| d.date_time >= day_start && d.date_time < day_end).for_each(|d| {
             mygrid.load.push(DataItem { x: d.date_time, y: to_kw(d.data, 1) });
         });
+
+        Ok(Some(mygrid))
+    } else {
+        Ok(None)
     }
+}
 
-    Ok(mygrid)
+/// Returns an empty `MygridData` struct
+///
+pub fn empty_mygrid_data() -> MygridData {
+    MygridData {
+        base_cost: 0.0,
+        schedule_cost: 0.0,
+        forecast_temp: Vec::new(),
+        forecast_cloud: Vec::new(),
+        prod: Vec::new(),
+        load: Vec::new(),
+        tariff_fees: TariffFees::default(),
+    }
 }
 
 /// Finds and reads the latest base data file that is equal to or older than the target time
@@ -145,6 +141,8 @@ fn transform_source_block(block: &SourceBlock) -> Block {
         start_time: block.start_time,
         end_time: block.end_time.add(TimeDelta::minutes(BLOCK_UNIT_SIZE)),
         length: format!("{:02}:{:02}", length.num_hours(), length.num_minutes() - length.num_hours() * 60),
+        planned_cost: block.cost,
+        actual: None,
     }
 }
 
@@ -2,6 +2,7 @@ use std::fmt;
 use std::fmt::Formatter;
 use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
+use crate::schedule_tracking::BlockActual;
 
 
 #[derive(Deserialize)]
@@ -56,7 +57,7 @@ pub struct BaseData {
 }
 
 /// Available block types
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
 pub enum BlockType {
     Charge,
     Hold,
@@ -125,6 +126,9 @@ pub struct Block {
     pub end_time: DateTime<Utc>,
     #[serde(default)]
     pub length: String,
+    #[serde(skip)]
+    pub planned_cost: f64,
+    pub actual: Option<BlockActual>,
 }
 
 
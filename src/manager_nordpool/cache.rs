@@ -0,0 +1,106 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use chrono::NaiveDate;
+use tracing::warn;
+use crate::manager_nordpool::NordPoolError;
+use crate::manager_nordpool::models::SpotPrice;
+
+/// On-disk cache of day-ahead spot prices, one file per provider, area, currency and delivery date.
+/// Only complete days are cached, so a cached day never has to be retrieved again
+///
+pub struct SpotCache {
+    path: PathBuf,
+}
+
+impl SpotCache {
+    /// Returns a new instance of SpotCache, the cache directory is created if needed
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - path to the directory where spot prices are cached
+    pub fn new(path: &str) -> Result<Self, NordPoolError> {
+        std::fs::create_dir_all(path)?;
+
+        Ok(Self { path: PathBuf::from(path) })
+    }
+
+    /// Returns cached spot prices, or None if the day isn't cached. A cache file that can't be
+    /// parsed is treated as missing, so that the day is retrieved and cached again
+    ///
+    /// # Arguments
+    ///
+    /// * 'provider' - name of the provider the prices come from
+    /// * 'area' - delivery area
+    /// * 'currency' - currency of the prices
+    /// * 'date' - delivery date
+    pub async fn get(&self, provider: &str, area: &str, currency: &str, date: NaiveDate) -> Result<Option<Vec<SpotPrice>>, NordPoolError> {
+        let path = self.file_path(provider, area, currency, date);
+        let json = match tokio::fs::read(&path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        match serde_json::from_slice(&json) {
+            Ok(prices) => Ok(Some(prices)),
+            Err(e) => {
+                warn!("ignoring corrupt spot price cache {}: {}", path.display(), e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Saves spot prices for a day. The file is written aside and then renamed into place, so
+    /// that a concurrent reader never sees a partially written file
+    ///
+    /// # Arguments
+    ///
+    /// * 'provider' - name of the provider the prices come from
+    /// * 'area' - delivery area
+    /// * 'currency' - currency of the prices
+    /// * 'date' - delivery date
+    /// * 'prices' - the spot prices
+    pub async fn save(&self, provider: &str, area: &str, currency: &str, date: NaiveDate, prices: &[SpotPrice]) -> Result<(), NordPoolError> {
+        let path = self.file_path(provider, area, currency, date);
+        let tmp_path = path.with_extension("tmp");
+
+        tokio::fs::write(&tmp_path, serde_json::to_vec(prices)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        Ok(())
+    }
+
+    /// Returns the path to the cache file for the given provider, area, currency and date
+    ///
+    /// # Arguments
+    ///
+    /// * 'provider' - name of the provider the prices come from
+    /// * 'area' - delivery area
+    /// * 'currency' - currency of the prices
+    /// * 'date' - delivery date
+    fn file_path(&self, provider: &str, area: &str, currency: &str, date: NaiveDate) -> PathBuf {
+        self.path.join(format!("{}_{}_{}_{}_spot.json", provider, area, currency, date.format("%Y%m%d")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use super::*;
+
+    #[tokio::test]
+    async fn providers_cached_apart() {
+        let path = std::env::temp_dir().join(format!("mygrid_dash_spot_cache_{}", std::process::id()));
+        let cache = SpotCache::new(&path.to_string_lossy()).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 10, 22).unwrap();
+        let prices = vec![SpotPrice { delivery_start: Utc.with_ymd_and_hms(2025, 10, 21, 22, 0, 0).unwrap(), price: 42.0 }];
+
+        cache.save("entsoe", "SE4", "EUR", date, &prices).await.unwrap();
+
+        assert_eq!(cache.get("entsoe", "SE4", "EUR", date).await.unwrap().unwrap()[0].price, 42.0);
+        assert!(cache.get("nordpool", "SE4", "EUR", date).await.unwrap().is_none());
+        assert!(path.join("entsoe_SE4_EUR_20251022_spot.json").exists());
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
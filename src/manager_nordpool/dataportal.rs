@@ -0,0 +1,72 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use reqwest::Client;
+use crate::manager_nordpool::NordPoolError;
+use crate::manager_nordpool::models::{SpotPrice, Tariffs};
+use crate::manager_nordpool::provider::SpotPriceProvider;
+
+/// Spot prices from Nord Pool's data portal API
+///
+pub struct DataPortal {
+    client: Client,
+    area: String,
+    currency: String,
+}
+
+impl DataPortal {
+    /// Returns a new instance of DataPortal
+    ///
+    /// # Arguments
+    ///
+    /// * 'area' - delivery area to retrieve prices for, e.g. SE4, FI or NO1
+    /// * 'currency' - currency to retrieve prices in, e.g. SEK or EUR
+    pub fn new(area: &str, currency: &str) -> Result<Self, NordPoolError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            area: area.to_string(),
+            currency: currency.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl SpotPriceProvider for DataPortal {
+    async fn get_spot_prices(&self, date: NaiveDate) -> Result<Vec<SpotPrice>, NordPoolError> {
+        // https://dataportal-api.nordpoolgroup.com/api/DayAheadPrices?date=2025-10-22&market=DayAhead&deliveryArea=SE4&currency=SEK
+        let url = "https://dataportal-api.nordpoolgroup.com/api/DayAheadPrices";
+        let date = format!("{}", date.format("%Y-%m-%d"));
+        let query = vec![
+            ("date", date.as_str()),
+            ("market", "DayAhead"),
+            ("deliveryArea", self.area.as_str()),
+            ("currency", self.currency.as_str()),
+        ];
+
+        let req = self.client.get(url).query(&query).send().await?;
+
+        let status = req.status();
+        if status.as_u16() == 204 {
+            return Err(NordPoolError::NoContentError)?;
+        }
+
+        let json = req.text().await?;
+
+        let tariffs: Tariffs = serde_json::from_str(&json)?;
+        tariffs.multi_area_entries
+            .iter()
+            .map(|t| t.entry_per_area
+                .get(&self.area)
+                .map(|&price| SpotPrice { delivery_start: t.delivery_start, price })
+                .ok_or_else(|| NordPoolError::AreaNotFoundError(self.area.clone())))
+            .collect::<Result<Vec<SpotPrice>, NordPoolError>>()
+    }
+
+    fn name(&self) -> &'static str {
+        "nordpool"
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use reqwest::{Client, StatusCode};
+use crate::initialization::ElprisetJustNu;
+use crate::manager_nordpool::NordPoolError;
+use crate::manager_nordpool::models::{ElprisetPrice, SpotPrice};
+use crate::manager_nordpool::provider::{day_bounds, fill_quarters, SpotPriceProvider};
+
+/// Spot prices from elprisetjustnu.se, or any API serving prices the same way.
+/// Only Swedish delivery areas, and prices in SEK or EUR, are available
+///
+pub struct Elpriset {
+    client: Client,
+    url: String,
+    area: String,
+    in_sek: bool,
+}
+
+impl Elpriset {
+    /// Returns a new instance of Elpriset
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - elprisetjustnu configuration
+    /// * 'area' - delivery area to retrieve prices for, SE1 to SE4
+    /// * 'currency' - currency to retrieve prices in, SEK or EUR
+    pub fn new(config: &ElprisetJustNu, area: &str, currency: &str) -> Result<Self, NordPoolError> {
+        if !["SE1", "SE2", "SE3", "SE4"].contains(&area) {
+            return Err(NordPoolError::AreaNotFoundError(area.to_string()));
+        }
+        let in_sek = match currency {
+            "SEK" => true,
+            "EUR" => false,
+            _ => return Err(NordPoolError::ProviderError(format!("elprisetjustnu has no prices in {}", currency))),
+        };
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: config.url.trim_end_matches('/').to_string(),
+            area: area.to_string(),
+            in_sek,
+        })
+    }
+}
+
+#[async_trait]
+impl SpotPriceProvider for Elpriset {
+    async fn get_spot_prices(&self, date: NaiveDate) -> Result<Vec<SpotPrice>, NordPoolError> {
+        // https://www.elprisetjustnu.se/api/v1/prices/2025/10-22_SE4.json
+        let url = format!("{}/{}_{}.json", self.url, date.format("%Y/%m-%d"), self.area);
+
+        let req = self.client.get(url).send().await?;
+        if req.status() == StatusCode::NOT_FOUND {
+            return Err(NordPoolError::NoContentError)?;
+        }
+
+        let json = req.error_for_status()?.text().await?;
+        let prices: Vec<ElprisetPrice> = serde_json::from_str(&json)?;
+        let prices = prices
+            .iter()
+            .map(|p| SpotPrice {
+                delivery_start: p.time_start.to_utc(),
+                price: (if self.in_sek { p.sek_per_kwh } else { p.eur_per_kwh }) * 1000.0,
+            })
+            .collect::<Vec<SpotPrice>>();
+
+        let (_, day_end) = day_bounds(date)?;
+
+        Ok(fill_quarters(&prices, day_end))
+    }
+
+    fn name(&self) -> &'static str {
+        "elprisetjustnu"
+    }
+}
@@ -0,0 +1,277 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use reqwest::Client;
+use crate::initialization::Entsoe;
+use crate::manager_nordpool::NordPoolError;
+use crate::manager_nordpool::models::{Period, PublicationMarketDocument, SpotPrice};
+use crate::manager_nordpool::provider::{day_bounds, fill_quarters, SpotPriceProvider};
+
+/// EIC codes for the Nordic delivery areas
+const DOMAINS: [(&str, &str); 12] = [
+    ("SE1", "10Y1001A1001A44P"),
+    ("SE2", "10Y1001A1001A45N"),
+    ("SE3", "10Y1001A1001A46L"),
+    ("SE4", "10Y1001A1001A47J"),
+    ("FI", "10YFI-1--------U"),
+    ("NO1", "10YNO-1--------2"),
+    ("NO2", "10YNO-2--------T"),
+    ("NO3", "10YNO-3--------J"),
+    ("NO4", "10YNO-4--------9"),
+    ("NO5", "10Y1001A1001A48H"),
+    ("DK1", "10YDK-1--------W"),
+    ("DK2", "10YDK-2--------M"),
+];
+
+/// Spot prices from the ENTSO-E transparency platform, which only has prices in EUR
+///
+pub struct EntsoeApi {
+    client: Client,
+    domain: String,
+    token: String,
+}
+
+impl EntsoeApi {
+    /// Returns a new instance of EntsoeApi
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - ENTSO-E configuration
+    /// * 'area' - delivery area to retrieve prices for, used if no domain is configured
+    /// * 'currency' - currency to retrieve prices in, must be EUR
+    pub fn new(config: &Entsoe, area: &str, currency: &str) -> Result<Self, NordPoolError> {
+        if currency != "EUR" {
+            return Err(NordPoolError::ProviderError(format!("ENTSO-E has no prices in {}, only in EUR", currency)));
+        }
+
+        let domain = match &config.domain {
+            Some(domain) => domain.clone(),
+            None => DOMAINS
+                .iter()
+                .find(|(a, _)| *a == area)
+                .map(|(_, domain)| domain.to_string())
+                .ok_or_else(|| NordPoolError::AreaNotFoundError(area.to_string()))?,
+        };
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            domain,
+            token: config.token.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl SpotPriceProvider for EntsoeApi {
+    async fn get_spot_prices(&self, date: NaiveDate) -> Result<Vec<SpotPrice>, NordPoolError> {
+        // https://web-api.tp.entsoe.eu/api?documentType=A44&in_Domain=10Y1001A1001A47J&out_Domain=10Y1001A1001A47J&periodStart=202510212200&periodEnd=202510222200
+        let url = "https://web-api.tp.entsoe.eu/api";
+        let (day_start, day_end) = day_bounds(date)?;
+        let period_start = format!("{}", day_start.format("%Y%m%d%H%M"));
+        let period_end = format!("{}", day_end.format("%Y%m%d%H%M"));
+        let query = vec![
+            ("securityToken", self.token.as_str()),
+            ("documentType", "A44"),
+            ("contract_MarketAgreement.type", "A01"),
+            ("in_Domain", self.domain.as_str()),
+            ("out_Domain", self.domain.as_str()),
+            ("periodStart", period_start.as_str()),
+            ("periodEnd", period_end.as_str()),
+        ];
+
+        let req = self.client.get(url).query(&query).send().await?;
+        let xml = req.text().await?;
+
+        parse_prices(&xml, day_start, day_end)
+    }
+
+    fn name(&self) -> &'static str {
+        "entsoe"
+    }
+}
+
+/// Parses spot prices for a day from an ENTSO-E day-ahead prices document, one per quarter.
+/// Points left out of a series since the price is unchanged are filled in
+///
+/// # Arguments
+///
+/// * 'xml' - the document as returned by ENTSO-E
+/// * 'day_start' - start of the day
+/// * 'day_end' - end of the day (non-inclusive)
+fn parse_prices(xml: &str, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> Result<Vec<SpotPrice>, NordPoolError> {
+    // ENTSO-E answers with an acknowledgement document, rather than a status, when it has no prices
+    if xml.contains("Acknowledgement_MarketDocument") {
+        return Err(NordPoolError::NoContentError);
+    }
+
+    let document: PublicationMarketDocument = quick_xml::de::from_str(xml)?;
+    let periods = document.time_series
+        .iter()
+        .flat_map(|t| t.periods.iter())
+        .map(|p| Ok((resolution(&p.resolution)?, p)))
+        .collect::<Result<Vec<(TimeDelta, &Period)>, NordPoolError>>()?;
+
+    // Areas on 15 minute prices also get hourly series, so only the finest are used
+    let Some(finest) = periods.iter().map(|(r, _)| *r).min() else {
+        return Err(NordPoolError::NoContentError);
+    };
+
+    let mut prices: Vec<SpotPrice> = Vec::new();
+    for (_, period) in periods.into_iter().filter(|(r, _)| *r == finest) {
+        let start = timestamp(&period.time_interval.start)?;
+        let end = timestamp(&period.time_interval.end)?;
+        let points = period.points
+            .iter()
+            .map(|p| SpotPrice { delivery_start: start + finest * (p.position as i32 - 1), price: p.price })
+            .collect::<Vec<SpotPrice>>();
+
+        prices.extend(fill_quarters(&points, end));
+    }
+    prices.sort_by_key(|p| p.delivery_start);
+    prices.dedup_by_key(|p| p.delivery_start);
+    prices.retain(|p| p.delivery_start >= day_start && p.delivery_start < day_end);
+
+    Ok(prices)
+}
+
+/// Parses an ENTSO-E timestamp, e.g. 2025-10-21T22:00Z
+///
+/// # Arguments
+///
+/// * 'timestamp' - the timestamp to parse
+fn timestamp(timestamp: &str) -> Result<DateTime<Utc>, NordPoolError> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%MZ")
+        .map(|t| t.and_utc())
+        .map_err(|e| NordPoolError::ProviderError(format!("timestamp {}: {}", timestamp, e)))
+}
+
+/// Parses an ENTSO-E resolution, e.g. PT15M
+///
+/// # Arguments
+///
+/// * 'resolution' - the resolution to parse
+fn resolution(resolution: &str) -> Result<TimeDelta, NordPoolError> {
+    resolution
+        .strip_prefix("PT")
+        .and_then(|r| r.strip_suffix('M'))
+        .and_then(|r| r.parse::<i64>().ok())
+        .filter(|&minutes| minutes > 0)
+        .map(TimeDelta::minutes)
+        .ok_or_else(|| NordPoolError::ProviderError(format!("unsupported resolution {}", resolution)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    fn day() -> (DateTime<Utc>, DateTime<Utc>) {
+        let day_start = Utc.with_ymd_and_hms(2025, 10, 21, 22, 0, 0).unwrap();
+        (day_start, day_start + TimeDelta::days(1))
+    }
+
+    fn document(series: &str) -> String {
+        format!(r#"<?xml version="1.0" encoding="utf-8"?>
+<Publication_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-3:publicationdocument:7:3">
+    <mRID>b2c5c8d1a3f54e7c</mRID>
+    <type>A44</type>
+    <period.timeInterval>
+        <start>2025-10-21T22:00Z</start>
+        <end>2025-10-22T22:00Z</end>
+    </period.timeInterval>
+    {}
+</Publication_MarketDocument>"#, series)
+    }
+
+    fn series(resolution: &str, points: &[(i64, f64)]) -> String {
+        let points = points
+            .iter()
+            .map(|(position, price)| format!("<Point><position>{}</position><price.amount>{}</price.amount></Point>", position, price))
+            .collect::<String>();
+
+        format!(r#"<TimeSeries>
+        <mRID>1</mRID>
+        <currency_Unit.name>EUR</currency_Unit.name>
+        <curveType>A03</curveType>
+        <Period>
+            <timeInterval>
+                <start>2025-10-21T22:00Z</start>
+                <end>2025-10-22T22:00Z</end>
+            </timeInterval>
+            <resolution>{}</resolution>
+            {}
+        </Period>
+    </TimeSeries>"#, resolution, points)
+    }
+
+    #[test]
+    fn quarter_prices_parsed_and_filled() {
+        let (day_start, day_end) = day();
+        let xml = document(&series("PT15M", &[(1, 10.5), (2, 12.0), (5, -1.25)]));
+
+        let prices = parse_prices(&xml, day_start, day_end).unwrap();
+
+        assert_eq!(prices.len(), 96);
+        assert_eq!((prices[0].delivery_start, prices[0].price), (day_start, 10.5));
+        // Positions 3 and 4 are left out, since unchanged from position 2
+        assert_eq!(prices[1..4].iter().map(|p| p.price).collect::<Vec<f64>>(), vec![12.0, 12.0, 12.0]);
+        assert_eq!(prices[4].delivery_start, day_start + TimeDelta::minutes(60));
+        assert_eq!(prices[95].price, -1.25);
+    }
+
+    #[test]
+    fn finest_resolution_used() {
+        let (day_start, day_end) = day();
+        let hourly = series("PT60M", &[(1, 100.0)]);
+        let quarterly = series("PT15M", &[(1, 10.0), (2, 20.0)]);
+        let xml = document(&format!("{}{}", hourly, quarterly));
+
+        let prices = parse_prices(&xml, day_start, day_end).unwrap();
+
+        assert_eq!(prices.len(), 96);
+        assert!(prices.iter().all(|p| p.price != 100.0));
+        assert_eq!(prices[1].price, 20.0);
+    }
+
+    #[test]
+    fn hourly_prices_spread_over_quarters() {
+        let (day_start, day_end) = day();
+        let xml = document(&series("PT60M", &[(1, 30.0), (2, 40.0)]));
+
+        let prices = parse_prices(&xml, day_start, day_end).unwrap();
+
+        assert_eq!(prices.len(), 96);
+        assert_eq!(prices[3].price, 30.0);
+        assert_eq!((prices[4].delivery_start, prices[4].price), (day_start + TimeDelta::hours(1), 40.0));
+    }
+
+    #[test]
+    fn acknowledgement_means_no_content() {
+        let (day_start, day_end) = day();
+        let xml = r#"<Acknowledgement_MarketDocument><Reason><code>999</code><text>No matching data found</text></Reason></Acknowledgement_MarketDocument>"#;
+
+        assert!(matches!(parse_prices(xml, day_start, day_end), Err(NordPoolError::NoContentError)));
+        assert!(matches!(parse_prices(&document(""), day_start, day_end), Err(NordPoolError::NoContentError)));
+    }
+
+    #[test]
+    fn unsupported_resolution_rejected() {
+        let (day_start, day_end) = day();
+        let xml = document(&series("P1D", &[(1, 30.0)]));
+
+        assert!(matches!(parse_prices(&xml, day_start, day_end), Err(NordPoolError::ProviderError(_))));
+    }
+
+    #[test]
+    fn timestamps_and_resolutions() {
+        assert_eq!(timestamp("2025-10-21T22:00Z").unwrap(), day().0);
+        assert!(timestamp("2025-10-21 22:00").is_err());
+        assert_eq!(resolution("PT15M").unwrap(), TimeDelta::minutes(15));
+        assert_eq!(resolution("PT60M").unwrap(), TimeDelta::hours(1));
+        assert!(resolution("PT0M").is_err());
+    }
+}
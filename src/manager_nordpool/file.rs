@@ -0,0 +1,60 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use crate::initialization::SpotPriceFile;
+use crate::manager_nordpool::NordPoolError;
+use crate::manager_nordpool::models::SpotPrice;
+use crate::manager_nordpool::provider::{day_bounds, fill_quarters, SpotPriceProvider};
+
+/// Spot prices from local files, one per area and date named e.g. SE4_20251022.json.
+/// Each file holds a list of prices (currency/MWh) with their delivery start, either per hour or
+/// per quarter, e.g. [{"delivery_start": "2025-10-21T22:00:00Z", "price": 431.2}, ...]
+///
+pub struct LocalFile {
+    path: PathBuf,
+    area: String,
+}
+
+impl LocalFile {
+    /// Returns a new instance of LocalFile
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - local file configuration
+    /// * 'area' - delivery area to read prices for
+    pub fn new(config: &SpotPriceFile, area: &str) -> Self {
+        Self {
+            path: PathBuf::from(&config.path),
+            area: area.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SpotPriceProvider for LocalFile {
+    async fn get_spot_prices(&self, date: NaiveDate) -> Result<Vec<SpotPrice>, NordPoolError> {
+        let path = self.path.join(format!("{}_{}.json", self.area, date.format("%Y%m%d")));
+        let json = match tokio::fs::read(&path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(NordPoolError::NoContentError),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut prices: Vec<SpotPrice> = serde_json::from_slice(&json)?;
+        prices.sort_by_key(|p| p.delivery_start);
+        let (_, day_end) = day_bounds(date)?;
+
+        Ok(fill_quarters(&prices, day_end))
+    }
+
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    /// Files are read as they are, so that changes to them are picked up
+    ///
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
@@ -1,31 +1,57 @@
 mod models;
-
-use std::time::Duration;
-use chrono::{DateTime, NaiveDate, TimeZone, Utc};
-use reqwest::Client;
+mod cache;
+mod dataportal;
+mod entsoe;
+mod elpriset;
+mod file;
+pub mod provider;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use anyhow::Result;
 use thiserror::Error;
+use tracing::{info, warn};
 use crate::models::{DataItem, TariffFees};
-use crate::manager_nordpool::models::Tariffs;
+use crate::manager_nordpool::cache::SpotCache;
+use crate::manager_nordpool::models::SpotPrice;
+use crate::manager_nordpool::provider::SpotPriceProvider;
+use crate::tariff_model::TariffModel;
+
+/// Least number of spot prices for a complete day, i.e. quarters in a day shortened by DST
+const MIN_DAY_ENTRIES: usize = 92;
 
+/// Day-ahead prices, from spot prices given by the configured provider with fees applied
+///
 pub struct NordPool {
-    client: Client,
+    provider: Box<dyn SpotPriceProvider>,
+    cache: SpotCache,
+    area: String,
+    currency: String,
     tariff_fees: Option<TariffFees>,
+    tariff_model: Box<dyn TariffModel>,
 }
 
 impl NordPool {
-    pub fn new() -> Result<NordPool, NordPoolError> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()?;
-
+    /// Returns a new instance of NordPool
+    ///
+    /// # Arguments
+    ///
+    /// * 'area' - delivery area to retrieve prices for, e.g. SE4, FI or NO1
+    /// * 'currency' - currency to retrieve prices in, e.g. SEK or EUR
+    /// * 'provider' - provider of spot prices
+    /// * 'tariff_model' - model turning spot prices into buy and sell prices
+    /// * 'cache_path' - path to the directory where spot prices are cached
+    pub fn new(area: &str, currency: &str, provider: Box<dyn SpotPriceProvider>, tariff_model: Box<dyn TariffModel>, cache_path: &str) -> Result<NordPool, NordPoolError> {
         Ok(Self {
-            client,
+            provider,
+            cache: SpotCache::new(cache_path)?,
+            area: area.to_string(),
+            currency: currency.to_string(),
             tariff_fees: None,
+            tariff_model,
         })
     }
 
-    /// Retrieves day ahead prices from NordPool
+    /// Retrieves day ahead prices from the spot price provider
     /// It gets the buy and sell tariffs for the day indicated by date_time (if it can't, an error will be returned),
     ///
     /// # Arguments
@@ -34,104 +60,132 @@ impl NordPool {
     /// * 'day_end' - the end time of the day to retrieve prices for (non-inclusive)
     /// * 'day_date' - the date to retrieve prices for
     pub async fn get_tariffs(&self, day_start: DateTime<Utc>, day_end: DateTime<Utc>, day_date: NaiveDate) -> Result<Option<(Vec<DataItem<f64>>, Vec<DataItem<f64>>)>, NordPoolError> {
-        if self.tariff_fees.is_some() {
-            let day_date_utc = TimeZone::from_utc_datetime(&Utc, &day_date.and_hms_opt(0,0,0).unwrap());
-            match self.get_day_tariffs(day_start, day_end, day_date_utc).await {
-                Ok(result) => Ok(Some(result)),
-                Err(e) if matches!(e, NordPoolError::NoContentError) => Ok(None),
-                Err(e) => Err(e),
-            }
+        if let Some(tariff_fees) = &self.tariff_fees {
+            self.get_tariffs_with_fees(day_start, day_end, day_date, tariff_fees).await
         } else {
             Ok(None)
         }
     }
 
+    /// Retrieves day ahead prices using the given tariff fees instead of the ones set,
+    /// which is useful when retrieving prices for days where other fees applied.
+    ///
+    /// None is returned if prices for the day aren't published yet, or if the provider so far
+    /// only has part of the day
+    ///
+    /// # Arguments
+    ///
+    /// * 'day_start' - the start time of the day to retrieve prices for
+    /// * 'day_end' - the end time of the day to retrieve prices for (non-inclusive)
+    /// * 'day_date' - the date to retrieve prices for
+    /// * 'tariff_fees' - the fees to add to the spot prices
+    pub async fn get_tariffs_with_fees(&self, day_start: DateTime<Utc>, day_end: DateTime<Utc>, day_date: NaiveDate, tariff_fees: &TariffFees) -> Result<Option<(Vec<DataItem<f64>>, Vec<DataItem<f64>>)>, NordPoolError> {
+        match self.get_day_tariffs(day_start, day_end, day_date, tariff_fees).await {
+            Ok(result) => Ok(Some(result)),
+            Err(NordPoolError::NoContentError) => {
+                info!("spot prices for {} not published yet", day_date);
+                Ok(None)
+            },
+            Err(NordPoolError::ContentLengthError(entries)) => {
+                warn!("spot prices for {} incomplete, got {} entries", day_date, entries);
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
     /// Sets tariff fees
     /// 
     /// # Arguments
     /// 
     /// * 'tariff_fees' - the fees
-    pub fn set_tariff_fees(&mut self, mut tariff_fees: TariffFees) {
-        tariff_fees.spot_fee_percentage /= 100.0;
+    pub fn set_tariff_fees(&mut self, tariff_fees: TariffFees) {
         self.tariff_fees = Some(tariff_fees);
     }
     
-    /// Retrieves day ahead prices from NordPool
+    /// Returns buy and sell prices for a day, from spot prices in the cache if there, otherwise
+    /// from spot prices retrieved from the provider, which are then cached.
+    /// Only complete days are used
     ///
     /// # Arguments
     ///
     /// * 'day_start' - the start time of the day to retrieve prices for
     /// * 'day_end' - the end time of the day to retrieve prices for (non-inclusive)
     /// * 'day_date' - the date to retrieve prices for
-    async fn get_day_tariffs(&self, day_start: DateTime<Utc>, day_end: DateTime<Utc>, day_date: DateTime<Utc>) -> Result<(Vec<DataItem<f64>>, Vec<DataItem<f64>>), NordPoolError> {
-        // https://dataportal-api.nordpoolgroup.com/api/DayAheadPrices?date=2025-10-22&market=DayAhead&deliveryArea=SE4&currency=SEK
-        let url = "https://dataportal-api.nordpoolgroup.com/api/DayAheadPrices";
-        let date = format!("{}", day_date.format("%Y-%m-%d"));
-        let query = vec![
-            ("date", date.as_str()),
-            ("market", "DayAhead"),
-            ("deliveryArea", "SE4"),
-            ("currency", "SEK"),
-        ];
-
-        let req = self.client.get(url).query(&query).send().await?;
-
-        let status = req.status();
-        if status.as_u16() == 204 {
-            return Err(NordPoolError::NoContentError)?;
+    /// * 'tariff_fees' - the fees to add to the spot prices
+    async fn get_day_tariffs(&self, day_start: DateTime<Utc>, day_end: DateTime<Utc>, day_date: NaiveDate, tariff_fees: &TariffFees) -> Result<(Vec<DataItem<f64>>, Vec<DataItem<f64>>), NordPoolError> {
+        if !self.provider.cacheable() {
+            let spot_prices = self.get_complete_day(day_date).await?;
+            return Ok(self.tariffs_to_vec(&spot_prices, day_start, day_end, tariff_fees));
         }
 
-        let json = req.text().await?;
+        let spot_prices = match self.cache.get(self.provider.name(), &self.area, &self.currency, day_date).await {
+            Ok(Some(spot_prices)) => spot_prices,
+            result => {
+                if let Err(e) = result {
+                    warn!("while reading spot price cache: {}", e);
+                }
+                let spot_prices = self.get_complete_day(day_date).await?;
+                if let Err(e) = self.cache.save(self.provider.name(), &self.area, &self.currency, day_date, &spot_prices).await {
+                    warn!("while saving spot price cache: {}", e);
+                }
+                spot_prices
+            }
+        };
 
-        let tariffs: Tariffs = serde_json::from_str(&json)?;
-        self.tariffs_to_vec(&tariffs, day_start, day_end)
+        Ok(self.tariffs_to_vec(&spot_prices, day_start, day_end, tariff_fees))
     }
 
-    /// Transforms the Tariffs struct to a plain vector of prices, one for buy and one for sell)
+    /// Returns spot prices from the provider, given that they cover the whole day
     ///
     /// # Arguments
     ///
-    /// * 'tariffs' - the struct containing prices
+    /// * 'day_date' - the date to retrieve prices for
+    async fn get_complete_day(&self, day_date: NaiveDate) -> Result<Vec<SpotPrice>, NordPoolError> {
+        let spot_prices = self.provider.get_spot_prices(day_date).await?;
+        if spot_prices.len() < MIN_DAY_ENTRIES {
+            return Err(NordPoolError::ContentLengthError(spot_prices.len()))?
+        }
+
+        Ok(spot_prices)
+    }
+
+    /// Transforms spot prices to plain vectors of prices, one for buy and one for sell
+    ///
+    /// # Arguments
+    ///
+    /// * 'spot_prices' - spot prices for the whole day
     /// * 'day_start' - start of day to transform tariffs for
     /// * 'day_end' - end of day to transform tariffs for (non-inclusive)
-    fn tariffs_to_vec(&self, tariffs: &Tariffs, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> Result<(Vec<DataItem<f64>>, Vec<DataItem<f64>>), NordPoolError> {
-        let entries = tariffs.multi_area_entries.len();
-        if entries < 92 {
-            return Err(NordPoolError::ContentLengthError)?
-        }
-        let day_avg = tariffs.multi_area_entries.iter().map(|t| t.entry_per_area.se4).sum::<f64>() / entries as f64 / 1000.0;
+    /// * 'tariff_fees' - the fees to add to the spot prices
+    fn tariffs_to_vec(&self, spot_prices: &[SpotPrice], day_start: DateTime<Utc>, day_end: DateTime<Utc>, tariff_fees: &TariffFees) -> (Vec<DataItem<f64>>, Vec<DataItem<f64>>) {
+        let day_avg = spot_prices.iter().map(|s| s.price).sum::<f64>() / spot_prices.len() as f64 / 1000.0;
 
         let mut result_buy: Vec<DataItem<f64>> = Vec::new();
         let mut result_sell: Vec<DataItem<f64>> = Vec::new();
-        tariffs.multi_area_entries.iter().filter(|t| t.delivery_start >= day_start && t.delivery_start < day_end).for_each(
-            |t| {
-                let (buy, sell) = self.add_vat_markup(day_avg, t.entry_per_area.se4, t.delivery_start);
+        spot_prices.iter().filter(|s| s.delivery_start >= day_start && s.delivery_start < day_end).for_each(
+            |s| {
+                let (buy, sell) = self.apply_tariff_model(tariff_fees, day_avg, s.price, s.delivery_start);
                 result_buy.push(buy);
                 result_sell.push(sell);
             });
 
-        Ok((result_buy, result_sell))
+        (result_buy, result_sell)
     }
 
-    /// Adds VAT and other markups such as energy taxes etc.
+    /// Applies the tariff model to a spot price.
     ///
     /// It delivers a tuple containing the buy and sell prices for the tariff.
     ///
     /// # Arguments
     ///
-    /// * 'day_avg' - average tariff for the day as from NordPool in SEK/MWh
-    /// * 'tariff' - spot fee as from NordPool in SEK/MWh
+    /// * 'fees' - the fees to add
+    /// * 'day_avg' - average tariff for the day in currency/kWh
+    /// * 'tariff' - spot fee as from the provider in currency/MWh
     /// * 'delivery_start' - start time for the spot
-    fn add_vat_markup(&self, day_avg: f64, tariff: f64, delivery_start: DateTime<Utc>) -> (DataItem<f64>, DataItem<f64>) {
-        let fees = self.tariff_fees.as_ref().unwrap();
-
-        let price = tariff / 1000.0; // SEK per MWh to per kWh
-        let grid_fees = (fees.variable_fee + fees.energy_tax) / 100.0 + fees.spot_fee_percentage * day_avg;
-        let trade_fees = (fees.swedish_power_grid + fees.balance_responsibility + fees.electric_certificate +
-            fees.guarantees_of_origin + fees.fixed) / 100.0 + price;
-
-        let buy = (grid_fees + trade_fees) / 0.8;
-        let sell = fees.production_price / 100.0 + price;
+    fn apply_tariff_model(&self, fees: &TariffFees, day_avg: f64, tariff: f64, delivery_start: DateTime<Utc>) -> (DataItem<f64>, DataItem<f64>) {
+        let price = tariff / 1000.0; // currency per MWh to per kWh
+        let (buy, sell) = self.tariff_model.prices(fees, day_avg, price, delivery_start);
 
         (
             DataItem { x: delivery_start, y: round_to_two_decimals(buy) },
@@ -158,6 +212,14 @@ pub enum NordPoolError {
     NetworkError(#[from] reqwest::Error),
     #[error("NoContentError")]
     NoContentError,
-    #[error("ContentLengthError")]
-    ContentLengthError,
+    #[error("ContentLengthError: {0} entries")]
+    ContentLengthError(usize),
+    #[error("AreaNotFoundError: {0}")]
+    AreaNotFoundError(String),
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("XmlError: {0}")]
+    XmlError(#[from] quick_xml::DeError),
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
 }
\ No newline at end of file
@@ -1,18 +1,13 @@
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
-
-#[derive(Deserialize, Debug)]
-pub struct EntryPerArea {
-    #[serde(rename = "SE4")]
-    pub se4: f64,
-}
+use std::collections::HashMap;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
 pub struct MultiAreaEntries {
     #[serde(rename = "deliveryStart")]
     pub delivery_start: DateTime<Utc>,
     #[serde(rename = "entryPerArea")]
-    pub entry_per_area: EntryPerArea,
+    pub entry_per_area: HashMap<String, f64>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -20,3 +15,53 @@ pub struct Tariffs {
     #[serde(rename = "multiAreaEntries")]
     pub multi_area_entries: Vec<MultiAreaEntries>,
 }
+
+/// Raw spot price for one delivery period, in currency/MWh
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpotPrice {
+    pub delivery_start: DateTime<Utc>,
+    pub price: f64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PublicationMarketDocument {
+    #[serde(rename = "TimeSeries", default)]
+    pub time_series: Vec<TimeSeries>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeSeries {
+    #[serde(rename = "Period", default)]
+    pub periods: Vec<Period>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Period {
+    #[serde(rename = "timeInterval")]
+    pub time_interval: TimeInterval,
+    pub resolution: String,
+    #[serde(rename = "Point", default)]
+    pub points: Vec<Point>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeInterval {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Point {
+    pub position: i64,
+    #[serde(rename = "price.amount")]
+    pub price: f64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ElprisetPrice {
+    #[serde(rename = "SEK_per_kWh")]
+    pub sek_per_kwh: f64,
+    #[serde(rename = "EUR_per_kWh")]
+    pub eur_per_kwh: f64,
+    pub time_start: DateTime<FixedOffset>,
+}
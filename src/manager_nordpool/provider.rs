@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeDelta, Utc};
+use crate::initialization::SpotPriceConfig;
+use crate::manager_nordpool::NordPoolError;
+use crate::manager_nordpool::dataportal::DataPortal;
+use crate::manager_nordpool::elpriset::Elpriset;
+use crate::manager_nordpool::entsoe::EntsoeApi;
+use crate::manager_nordpool::file::LocalFile;
+use crate::manager_nordpool::models::SpotPrice;
+
+/// Resolution spot prices are delivered in by all providers
+const QUARTER: TimeDelta = TimeDelta::minutes(15);
+
+/// A source of day-ahead spot prices
+///
+#[async_trait]
+pub trait SpotPriceProvider: Send + Sync {
+    /// Returns spot prices (currency/MWh) for a day, one per quarter.
+    /// Prices not yet published gives a NoContentError
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the date to get prices for
+    async fn get_spot_prices(&self, date: NaiveDate) -> Result<Vec<SpotPrice>, NordPoolError>;
+
+    /// Returns the name of the provider, which keeps prices from different providers apart
+    /// in the cache
+    ///
+    fn name(&self) -> &'static str;
+
+    /// Returns whether prices from the provider should be cached
+    ///
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the spot price provider configured
+///
+/// # Arguments
+///
+/// * 'config' - spot price provider configuration
+/// * 'area' - delivery area to get prices for, e.g. SE4, FI or NO1
+/// * 'currency' - currency to get prices in, e.g. SEK or EUR
+pub fn get_spot_price_provider(config: &SpotPriceConfig, area: &str, currency: &str) -> Result<Box<dyn SpotPriceProvider>, NordPoolError> {
+    Ok(match config {
+        SpotPriceConfig::NordPool => Box::new(DataPortal::new(area, currency)?),
+        SpotPriceConfig::Entsoe(entsoe) => Box::new(EntsoeApi::new(entsoe, area, currency)?),
+        SpotPriceConfig::ElprisetJustNu(elpriset) => Box::new(Elpriset::new(elpriset, area, currency)?),
+        SpotPriceConfig::File(file) => Box::new(LocalFile::new(file, area)),
+    })
+}
+
+/// Returns start and end (non-inclusive) of the local day
+///
+/// # Arguments
+///
+/// * 'date' - the date of the day
+pub fn day_bounds(date: NaiveDate) -> Result<(DateTime<Utc>, DateTime<Utc>), NordPoolError> {
+    let local_midnight = |date: NaiveDate| date
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|d| d.to_utc());
+
+    local_midnight(date)
+        .zip(date.checked_add_days(Days::new(1)).and_then(local_midnight))
+        .ok_or_else(|| NordPoolError::ProviderError(format!("no local day bounds for {}", date)))
+}
+
+/// Spreads prices over quarters, each price covering the quarters up to the next price or the end.
+/// Prices given per hour, or left out while unchanged, thus end up as one price per quarter
+///
+/// # Arguments
+///
+/// * 'prices' - prices ordered by delivery start
+/// * 'end' - end of the last price (non-inclusive)
+pub fn fill_quarters(prices: &[SpotPrice], end: DateTime<Utc>) -> Vec<SpotPrice> {
+    let mut result: Vec<SpotPrice> = Vec::new();
+    for (i, spot) in prices.iter().enumerate() {
+        let span_end = prices.get(i + 1).map_or(end, |next| next.delivery_start);
+        let mut delivery_start = spot.delivery_start;
+        while delivery_start < span_end {
+            result.push(SpotPrice { delivery_start, price: spot.price });
+            delivery_start += QUARTER;
+        }
+    }
+
+    result
+}
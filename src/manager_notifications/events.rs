@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use crate::manager_mygrid::models::{Block, Status};
+use crate::manager_notifications::models::{Event, EventKind};
+use crate::models::{DataItem, TariffColor};
+use crate::usage_policy::PolicyReason;
+
+/// SoC percentage points above the low SoC threshold needed before low SoC is notified again
+const LOW_SOC_HYSTERESIS: u8 = 5;
+
+/// Keeps track of what has been observed so far, so that events are raised on changes only.
+///
+/// Nothing is raised on the first observation of a value, so that a restart doesn't repeat
+/// notifications already sent
+pub struct EventTracker {
+    low_soc: u8,
+    soh_drop: u8,
+    policy: Option<TariffColor>,
+    red_notified: bool,
+    low_soc_armed: Option<bool>,
+    soh_baseline: Option<u8>,
+    error_blocks: Option<HashSet<DateTime<Utc>>>,
+    tomorrow_missing: Option<NaiveDate>,
+}
+
+impl EventTracker {
+    /// Returns a new instance of EventTracker
+    ///
+    /// # Arguments
+    ///
+    /// * 'low_soc' - SoC (%) below which low SoC is raised
+    /// * 'soh_drop' - drop in SoH (percentage points) that raises an SoH drop
+    pub fn new(low_soc: u8, soh_drop: u8) -> Self {
+        Self {
+            low_soc,
+            soh_drop: soh_drop.max(1),
+            policy: None,
+            red_notified: false,
+            low_soc_armed: None,
+            soh_baseline: None,
+            error_blocks: None,
+            tomorrow_missing: None,
+        }
+    }
+
+    /// Raises an event when the usage policy turns red, and when it's back to green after having
+    /// been red
+    ///
+    /// # Arguments
+    ///
+    /// * 'color' - the current usage policy
+    /// * 'reason' - the reason for the current usage policy
+    pub fn policy(&mut self, color: &TariffColor, reason: Option<&PolicyReason>) -> Option<Event> {
+        let previous = self.policy.replace(color.clone())?;
+        if previous == *color {
+            return None;
+        }
+        let reason = reason.map_or(String::new(), |r| r.to_string());
+
+        match color {
+            TariffColor::Red => {
+                self.red_notified = true;
+                Some(Event::new(EventKind::PolicyRed, "Usage policy is red", reason))
+            },
+            TariffColor::Green if self.red_notified => {
+                self.red_notified = false;
+                Some(Event::new(EventKind::PolicyGreen, "Usage policy is back to green", reason))
+            },
+            _ => None,
+        }
+    }
+
+    /// Raises an event when SoC falls below the threshold, it is raised again only after SoC
+    /// has been back above the threshold with some margin
+    ///
+    /// # Arguments
+    ///
+    /// * 'soc' - current SoC
+    pub fn soc(&mut self, soc: u8) -> Option<Event> {
+        let Some(armed) = self.low_soc_armed else {
+            self.low_soc_armed = Some(soc >= self.low_soc);
+            return None;
+        };
+
+        if armed && soc < self.low_soc {
+            self.low_soc_armed = Some(false);
+            Some(Event::new(EventKind::LowSoc, "Battery is low", format!("SoC is {}%, below {}%", soc, self.low_soc)))
+        } else {
+            if soc >= self.low_soc.saturating_add(LOW_SOC_HYSTERESIS) {
+                self.low_soc_armed = Some(true);
+            }
+            None
+        }
+    }
+
+    /// Raises an event when SoH has dropped by at least the configured percentage points since
+    /// the last time it was raised, or since the first observation
+    ///
+    /// # Arguments
+    ///
+    /// * 'soh' - current SoH
+    pub fn soh(&mut self, soh: u8) -> Option<Event> {
+        let baseline = *self.soh_baseline.get_or_insert(soh);
+
+        if soh > baseline {
+            self.soh_baseline = Some(soh);
+            None
+        } else if baseline - soh >= self.soh_drop {
+            self.soh_baseline = Some(soh);
+            Some(Event::new(EventKind::SohDrop, "Battery health dropped", format!("SoH dropped from {}% to {}%", baseline, soh)))
+        } else {
+            None
+        }
+    }
+
+    /// Raises one event per schedule block that has entered error status
+    ///
+    /// # Arguments
+    ///
+    /// * 'schedule' - the current schedule
+    pub fn schedule(&mut self, schedule: &[Block]) -> Vec<Event> {
+        let error_status = Status::Error.to_string();
+        let in_error = schedule
+            .iter()
+            .filter(|b| b.status == error_status)
+            .collect::<Vec<&Block>>();
+
+        let Some(notified) = self.error_blocks.as_mut() else {
+            self.error_blocks = Some(in_error.iter().map(|b| b.start_time).collect());
+            return Vec::new();
+        };
+
+        in_error
+            .into_iter()
+            .filter(|b| notified.insert(b.start_time))
+            .map(|b| Event::new(
+                EventKind::BlockError,
+                "Schedule block failed",
+                format!("{:?} block starting {} is in error", b.block_type, b.start_time.with_timezone(&Local).format("%H:%M")),
+            ))
+            .collect()
+    }
+
+    /// Raises an event when tomorrow's tariffs arrive after having been missing
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - tomorrow's local date
+    /// * 'tariffs' - tomorrow's buy tariffs if known
+    pub fn tomorrow_tariffs(&mut self, date: NaiveDate, tariffs: Option<&Vec<DataItem<f64>>>) -> Option<Event> {
+        let Some(tariffs) = tariffs.filter(|t| !t.is_empty()) else {
+            self.tomorrow_missing = Some(date);
+            return None;
+        };
+        if self.tomorrow_missing != Some(date) {
+            return None;
+        }
+        self.tomorrow_missing = None;
+
+        let min = tariffs.iter().map(|t| t.y).fold(f64::INFINITY, f64::min);
+        let max = tariffs.iter().map(|t| t.y).fold(f64::NEG_INFINITY, f64::max);
+        let avg = tariffs.iter().map(|t| t.y).sum::<f64>() / tariffs.len() as f64;
+
+        Some(Event::new(
+            EventKind::TomorrowTariffs,
+            "Tomorrow's tariffs are available",
+            format!("Tariffs for {}: min {:.2}, avg {:.2}, max {:.2}", date.format("%Y-%m-%d"), min, avg, max),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tariffs(date: NaiveDate) -> Vec<DataItem<f64>> {
+        let start = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        (0..4).map(|h| DataItem { x: start + chrono::TimeDelta::hours(h), y: h as f64 }).collect()
+    }
+
+    #[test]
+    fn tomorrow_tariffs_notified_once_when_published() {
+        let mut tracker = EventTracker::new(20, 2);
+        let date = NaiveDate::from_ymd_opt(2025, 10, 22).unwrap();
+        let published = tariffs(date);
+
+        assert!(tracker.tomorrow_tariffs(date, None).is_none());
+        assert!(tracker.tomorrow_tariffs(date, None).is_none());
+
+        let event = tracker.tomorrow_tariffs(date, Some(&published)).unwrap();
+        assert_eq!(event.kind, EventKind::TomorrowTariffs);
+        assert!(event.message.contains("min 0.00, avg 1.50, max 3.00"));
+
+        assert!(tracker.tomorrow_tariffs(date, Some(&published)).is_none());
+    }
+
+    #[test]
+    fn tomorrow_tariffs_not_notified_when_present_from_start() {
+        let mut tracker = EventTracker::new(20, 2);
+        let date = NaiveDate::from_ymd_opt(2025, 10, 22).unwrap();
+
+        assert!(tracker.tomorrow_tariffs(date, Some(&tariffs(date))).is_none());
+    }
+
+    #[test]
+    fn tomorrow_tariffs_notified_again_next_day() {
+        let mut tracker = EventTracker::new(20, 2);
+        let date = NaiveDate::from_ymd_opt(2025, 10, 22).unwrap();
+        let next = date.succ_opt().unwrap();
+
+        tracker.tomorrow_tariffs(date, None);
+        assert!(tracker.tomorrow_tariffs(date, Some(&tariffs(date))).is_some());
+
+        tracker.tomorrow_tariffs(next, None);
+        assert!(tracker.tomorrow_tariffs(next, Some(&tariffs(next))).is_some());
+    }
+
+    #[test]
+    fn policy_red_then_green() {
+        let mut tracker = EventTracker::new(20, 2);
+
+        assert!(tracker.policy(&TariffColor::Green, None).is_none());
+        assert_eq!(tracker.policy(&TariffColor::Red, None).map(|e| e.kind), Some(EventKind::PolicyRed));
+        assert!(tracker.policy(&TariffColor::Red, None).is_none());
+        assert!(tracker.policy(&TariffColor::Yellow, None).is_none());
+        assert_eq!(tracker.policy(&TariffColor::Green, None).map(|e| e.kind), Some(EventKind::PolicyGreen));
+    }
+
+    #[test]
+    fn policy_green_without_red_not_notified() {
+        let mut tracker = EventTracker::new(20, 2);
+
+        assert!(tracker.policy(&TariffColor::Green, None).is_none());
+        assert!(tracker.policy(&TariffColor::Yellow, None).is_none());
+        assert!(tracker.policy(&TariffColor::Green, None).is_none());
+    }
+
+    #[test]
+    fn low_soc_with_hysteresis() {
+        let mut tracker = EventTracker::new(20, 2);
+
+        assert!(tracker.soc(50).is_none());
+        assert_eq!(tracker.soc(19).map(|e| e.kind), Some(EventKind::LowSoc));
+        assert!(tracker.soc(18).is_none());
+        assert!(tracker.soc(22).is_none());
+        assert!(tracker.soc(19).is_none());
+        assert!(tracker.soc(25).is_none());
+        assert_eq!(tracker.soc(19).map(|e| e.kind), Some(EventKind::LowSoc));
+    }
+
+    #[test]
+    fn low_soc_at_start_not_notified() {
+        let mut tracker = EventTracker::new(20, 2);
+
+        assert!(tracker.soc(10).is_none());
+        assert!(tracker.soc(9).is_none());
+    }
+
+    #[test]
+    fn soh_drop_from_baseline() {
+        let mut tracker = EventTracker::new(20, 2);
+
+        assert!(tracker.soh(98).is_none());
+        assert!(tracker.soh(97).is_none());
+        assert_eq!(tracker.soh(96).map(|e| e.kind), Some(EventKind::SohDrop));
+        assert!(tracker.soh(95).is_none());
+        assert_eq!(tracker.soh(94).map(|e| e.kind), Some(EventKind::SohDrop));
+    }
+}
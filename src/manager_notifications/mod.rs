@@ -0,0 +1,229 @@
+pub mod events;
+pub mod models;
+
+use std::time::Duration;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use reqwest::Client;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+use crate::initialization::{Notifications, SmtpTls};
+use crate::manager_notifications::models::{Event, EventKind};
+
+/// Notification manager
+///
+/// Events are queued and delivered to all configured sinks by a background task, so that a slow
+/// or failing sink never holds up the caller. The task ends when the notifier is dropped
+pub struct Notifier {
+    tx: Option<mpsc::UnboundedSender<Event>>,
+    events: Vec<EventKind>,
+}
+
+impl Notifier {
+    /// Returns a new instance of Notifier, the delivery task is only started if any sink
+    /// is configured
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - notifications configuration
+    pub fn new(config: &Notifications) -> Result<Self, NotificationError> {
+        let sinks = get_sinks(config)?;
+        if sinks.is_empty() {
+            return Ok(Self { tx: None, events: Vec::new() });
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                for sink in &sinks {
+                    if let Err(e) = sink.send(&event).await {
+                        error!("while sending {:?} notification to {}: {}", event.kind, sink.name(), e);
+                    }
+                }
+            }
+        });
+
+        Ok(Self { tx: Some(tx), events: config.events.clone() })
+    }
+
+    /// Returns whether the given kind of event is to be notified about
+    ///
+    /// # Arguments
+    ///
+    /// * 'kind' - kind of event
+    pub fn is_enabled(&self, kind: EventKind) -> bool {
+        self.tx.is_some() && self.events.contains(&kind)
+    }
+
+    /// Queues an event for delivery if its kind is to be notified about
+    ///
+    /// # Arguments
+    ///
+    /// * 'event' - the event to notify about
+    pub fn notify(&self, event: Event) {
+        if !self.is_enabled(event.kind) {
+            return;
+        }
+        info!("notifying about {:?}: {}", event.kind, event.title);
+
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+/// Sends a test notification to each configured sink directly, for checking the configuration
+/// against real or local stand-in servers
+///
+/// # Arguments
+///
+/// * 'config' - notifications configuration
+pub async fn send_test(config: &Notifications) -> Result<(), NotificationError> {
+    let sinks = get_sinks(config)?;
+    if sinks.is_empty() {
+        return Err(NotificationError::SinkError("no notification sinks configured".to_string()));
+    }
+
+    let event = Event::new(EventKind::Test, "Test notification", "This is a test notification from mygrid_dash".to_string());
+    for sink in &sinks {
+        sink.send(&event).await?;
+        info!("test notification sent to {}", sink.name());
+    }
+
+    Ok(())
+}
+
+/// A destination for notifications
+///
+enum Sink {
+    Webhook { client: Client, url: String },
+    Ntfy { client: Client, url: String, token: Option<String> },
+    Smtp { transport: Box<AsyncSmtpTransport<Tokio1Executor>>, from: Mailbox, to: Vec<Mailbox> },
+}
+
+impl Sink {
+    /// Returns the name of the sink for logging
+    ///
+    fn name(&self) -> &'static str {
+        match self {
+            Sink::Webhook { .. } => "webhook",
+            Sink::Ntfy { .. } => "ntfy",
+            Sink::Smtp { .. } => "smtp",
+        }
+    }
+
+    /// Delivers an event
+    ///
+    /// # Arguments
+    ///
+    /// * 'event' - the event to deliver
+    async fn send(&self, event: &Event) -> Result<(), NotificationError> {
+        match self {
+            Sink::Webhook { client, url } => {
+                let res = client.post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(serde_json::to_string(event)?)
+                    .send().await?;
+                if !res.status().is_success() {
+                    return Err(NotificationError::SinkError(format!("webhook response with status: {:?}", res.status())));
+                }
+            },
+            Sink::Ntfy { client, url, token } => {
+                let mut req = client.post(url)
+                    .header("Title", &event.title)
+                    .header("Tags", format!("{:?}", event.kind).to_lowercase())
+                    .header("Priority", if event.is_urgent() { "high" } else { "default" })
+                    .body(event.message.clone());
+                if let Some(token) = token {
+                    req = req.bearer_auth(token);
+                }
+
+                let res = req.send().await?;
+                if !res.status().is_success() {
+                    return Err(NotificationError::SinkError(format!("ntfy response with status: {:?}", res.status())));
+                }
+            },
+            Sink::Smtp { transport, from, to } => {
+                let mut builder = Message::builder()
+                    .from(from.clone())
+                    .subject(&event.title)
+                    .header(ContentType::TEXT_PLAIN);
+                for mailbox in to {
+                    builder = builder.to(mailbox.clone());
+                }
+
+                transport.send(builder.body(event.message.clone())?).await?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns all sinks configured
+///
+/// # Arguments
+///
+/// * 'config' - notifications configuration
+fn get_sinks(config: &Notifications) -> Result<Vec<Sink>, NotificationError> {
+    let mut sinks: Vec<Sink> = Vec::new();
+
+    if config.webhook.is_some() || config.ntfy.is_some() {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        if let Some(webhook) = &config.webhook {
+            sinks.push(Sink::Webhook { client: client.clone(), url: webhook.url.clone() });
+        }
+        if let Some(ntfy) = &config.ntfy {
+            let url = format!("{}/{}", ntfy.url.trim_end_matches('/'), ntfy.topic);
+            sinks.push(Sink::Ntfy { client, url, token: ntfy.token.clone() });
+        }
+    }
+
+    if let Some(smtp) = &config.smtp {
+        let builder = match smtp.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host),
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)?,
+        };
+        let builder = builder
+            .port(smtp.port)
+            .timeout(Some(Duration::from_secs(30)));
+        let transport = match &smtp.username {
+            Some(username) => builder.credentials(Credentials::new(username.clone(), smtp.password.clone())).build(),
+            None => builder.build(),
+        };
+
+        let from = smtp.from.parse::<Mailbox>()?;
+        let to = smtp.to
+            .iter()
+            .map(|to| to.parse::<Mailbox>())
+            .collect::<Result<Vec<Mailbox>, _>>()?;
+
+        sinks.push(Sink::Smtp { transport: Box::new(transport), from, to });
+    }
+
+    Ok(sinks)
+}
+
+#[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
+pub enum NotificationError {
+    #[error("ReqwestError: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("SerdeJsonError: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error("SmtpError: {0}")]
+    SmtpError(#[from] lettre::transport::smtp::Error),
+    #[error("EmailError: {0}")]
+    EmailError(#[from] lettre::error::Error),
+    #[error("AddressError: {0}")]
+    AddressError(#[from] lettre::address::AddressError),
+    #[error("SinkError: {0}")]
+    SinkError(String),
+}
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Kinds of events that can be notified about
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    PolicyRed,
+    PolicyGreen,
+    LowSoc,
+    SohDrop,
+    BlockError,
+    TomorrowTariffs,
+    Test,
+}
+
+/// An event to notify about, also used as payload for webhooks
+#[derive(Serialize, Clone)]
+pub struct Event {
+    pub kind: EventKind,
+    pub title: String,
+    pub message: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Event {
+    /// Returns a new event stamped with the current time
+    ///
+    /// # Arguments
+    ///
+    /// * 'kind' - kind of event
+    /// * 'title' - short title
+    /// * 'message' - message text
+    pub fn new(kind: EventKind, title: &str, message: String) -> Self {
+        Self { kind, title: title.to_string(), message, timestamp: Utc::now() }
+    }
+
+    /// Returns whether the event is urgent enough to be pushed with high priority
+    ///
+    pub fn is_urgent(&self) -> bool {
+        matches!(self.kind, EventKind::PolicyRed | EventKind::LowSoc | EventKind::BlockError)
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Metrics exposed in the Prometheus text format.
+/// The dispatcher hands over its metrics pre-rendered after each update, while the web server
+/// keeps count of logins
+///
+#[derive(Default)]
+pub struct Metrics {
+    login_successes: AtomicU64,
+    login_failures: AtomicU64,
+    dispatcher: Mutex<String>,
+}
+
+impl Metrics {
+    /// Counts a login attempt
+    ///
+    /// # Arguments
+    ///
+    /// * 'success' - whether the login succeeded or not
+    pub fn login(&self, success: bool) {
+        if success {
+            self.login_successes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.login_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Replaces the metrics rendered by the dispatcher
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - dispatcher metrics in the Prometheus text format
+    pub fn set_dispatcher_metrics(&self, text: String) {
+        *self.dispatcher.lock().unwrap() = text;
+    }
+
+    /// Returns all metrics in the Prometheus text format
+    ///
+    /// # Arguments
+    ///
+    /// * 'sessions' - number of logged in sessions
+    pub fn render(&self, sessions: usize) -> String {
+        let mut text = MetricsText::new();
+        text.metric("mygrid_dash_sessions", "gauge", "Number of logged in sessions", &[("", sessions as f64)]);
+        text.metric("mygrid_dash_logins_total", "counter", "Login attempts by outcome", &[
+            ("result=\"success\"", self.login_successes.load(Ordering::Relaxed) as f64),
+            ("result=\"failure\"", self.login_failures.load(Ordering::Relaxed) as f64),
+        ]);
+
+        let mut text = text.into_string();
+        text.push_str(&self.dispatcher.lock().unwrap());
+
+        text
+    }
+}
+
+/// Builder for metrics in the Prometheus text format
+///
+pub struct MetricsText(String);
+
+impl MetricsText {
+    /// Returns an empty builder
+    ///
+    pub fn new() -> Self {
+        Self(String::new())
+    }
+
+    /// Adds a metric family with its help and type lines followed by its samples
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - metric name
+    /// * 'kind' - metric type, e.g. gauge or counter
+    /// * 'help' - description of the metric
+    /// * 'samples' - label set (without braces, empty for none) and value per sample
+    pub fn metric<S: AsRef<str>>(&mut self, name: &str, kind: &str, help: &str, samples: &[(S, f64)]) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            self.sample(name, labels.as_ref(), *value);
+        }
+    }
+
+    /// Adds a single sample without help and type lines, e.g. the sum and count of a summary
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - sample name
+    /// * 'labels' - label set without braces, empty for none
+    /// * 'value' - sample value
+    pub fn sample(&mut self, name: &str, labels: &str, value: f64) {
+        if labels.is_empty() {
+            let _ = writeln!(self.0, "{} {}", name, value);
+        } else {
+            let _ = writeln!(self.0, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+
+    /// Returns the metrics built so far
+    ///
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
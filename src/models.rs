@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, PartialEq, Eq, Clone)]
 pub enum TariffColor {
@@ -76,7 +76,8 @@ pub struct RealTimeData {
     pub timestamp: i64,
 }
 
-#[derive(Clone)]
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct TariffFees {
     pub variable_fee: f64,
     pub spot_fee_percentage: f64,
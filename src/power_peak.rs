@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use crate::initialization::PowerPeak;
+use crate::manager_history::models::StoredInterval;
+use crate::models::{DataItem, TariffColor};
+use crate::tariff_model::in_window;
+
+/// The month's highest hourly average import so far, and where the current hour is heading
+///
+#[derive(Clone)]
+pub struct PeakStatus {
+    pub peak: Option<DataItem<f64>>,
+    pub projected: f64,
+    pub color: TariffColor,
+}
+
+/// Returns the highest hourly average import power (kW) among hours that have ended and that
+/// count for the power peak
+///
+/// # Arguments
+///
+/// * 'intervals' - energy intervals
+/// * 'config' - power peak configuration
+/// * 'before' - hours ending after this are left out
+pub fn get_peak(intervals: &[StoredInterval], config: &PowerPeak, before: DateTime<Utc>) -> Option<DataItem<f64>> {
+    hourly_imports(intervals)
+        .into_iter()
+        .filter(|(hour, _)| *hour + TimeDelta::hours(1) <= before && counts(config, *hour))
+        .map(|(hour, power)| DataItem { x: hour, y: round(power) })
+        .max_by(|a, b| a.y.total_cmp(&b.y))
+}
+
+/// Returns the highest of two peaks
+///
+/// # Arguments
+///
+/// * 'a' - a peak
+/// * 'b' - another peak
+pub fn max_peak(a: Option<DataItem<f64>>, b: Option<DataItem<f64>>) -> Option<DataItem<f64>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.y > a.y { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Projects the average import power for the current hour from what has been imported so far
+/// during it, assuming the current import continues for the rest of the hour. The color is
+/// yellow when the projection is within the warning ratio of the peak, and red when it would
+/// set a new peak. Projections below the minimum power, and hours not counting for the power
+/// peak, are always green
+///
+/// # Arguments
+///
+/// * 'intervals' - today's energy intervals
+/// * 'peak' - the month's peak so far
+/// * 'grid_power' - current grid power, negative when importing
+/// * 'config' - power peak configuration
+/// * 'utc_now' - 'now' according to the Utc timezone
+pub fn get_peak_status(intervals: &[StoredInterval], peak: Option<DataItem<f64>>, grid_power: f64, config: &PowerPeak, utc_now: DateTime<Utc>) -> PeakStatus {
+    let Ok(hour) = utc_now.duration_trunc(TimeDelta::hours(1)) else {
+        return PeakStatus { peak, projected: 0.0, color: TariffColor::Green };
+    };
+
+    let imported = intervals
+        .iter()
+        .filter(|i| i.from_ts >= hour && i.to_ts <= utc_now)
+        .map(|i| i.grid_consumption_energy)
+        .sum::<f64>();
+    let covered = intervals
+        .iter()
+        .filter(|i| i.from_ts >= hour && i.to_ts <= utc_now)
+        .map(|i| i.to_ts)
+        .max()
+        .unwrap_or(hour);
+    let remaining = (hour + TimeDelta::hours(1) - covered).num_seconds() as f64 / 3600.0;
+    let projected = round(imported + (-grid_power).max(0.0) * remaining);
+
+    let peak_power = peak.as_ref().map_or(0.0, |p| p.y);
+    let color = if !config.enabled || projected < config.min_power || !counts(config, hour) {
+        TariffColor::Green
+    } else if projected > peak_power {
+        TariffColor::Red
+    } else if projected >= peak_power * config.warning_ratio {
+        TariffColor::Yellow
+    } else {
+        TariffColor::Green
+    };
+
+    PeakStatus { peak, projected, color }
+}
+
+/// Returns imported energy per hour, which equals the hour's average import power
+///
+/// # Arguments
+///
+/// * 'intervals' - energy intervals
+fn hourly_imports(intervals: &[StoredInterval]) -> BTreeMap<DateTime<Utc>, f64> {
+    let mut hours: BTreeMap<DateTime<Utc>, f64> = BTreeMap::new();
+    for interval in intervals {
+        if let Ok(hour) = interval.from_ts.duration_trunc(TimeDelta::hours(1)) {
+            *hours.entry(hour).or_default() += interval.grid_consumption_energy;
+        }
+    }
+
+    hours
+}
+
+/// Returns whether the hour counts for the power peak, all hours do if no windows are configured
+///
+/// # Arguments
+///
+/// * 'config' - power peak configuration
+/// * 'hour' - start of the hour
+fn counts(config: &PowerPeak, hour: DateTime<Utc>) -> bool {
+    config.windows.is_empty() || config.windows.iter().any(|w| in_window(w, hour))
+}
+
+/// Rounds to two decimals
+///
+/// # Arguments
+///
+/// * 'value' - the value to round
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use crate::initialization::TimeWindow;
+    use super::*;
+
+    fn hour() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 22, 10, 0, 0).unwrap()
+    }
+
+    fn interval(quarter: i64, grid_consumption_energy: f64) -> StoredInterval {
+        let from_ts = hour() + TimeDelta::minutes(15 * quarter);
+        StoredInterval {
+            from_ts,
+            to_ts: from_ts + TimeDelta::minutes(15),
+            feed_in_energy: 0.0,
+            grid_consumption_energy,
+            tariff_buy: 0.0,
+            tariff_sell: 0.0,
+            bought: 0.0,
+            sold: 0.0,
+        }
+    }
+
+    fn config() -> PowerPeak {
+        PowerPeak { enabled: true, ..PowerPeak::default() }
+    }
+
+    fn never() -> TimeWindow {
+        TimeWindow { from_hour: 0, to_hour: 0, weekdays: Vec::new(), months: Vec::new() }
+    }
+
+    #[test]
+    fn peak_is_highest_completed_hour() {
+        let intervals = (0..12).map(|q| interval(q, if q < 4 { 1.0 } else { 0.5 })).collect::<Vec<StoredInterval>>();
+
+        let peak = get_peak(&intervals, &config(), hour() + TimeDelta::hours(3)).unwrap();
+        assert_eq!((peak.x, peak.y), (hour(), 4.0));
+
+        // Only the first hour has ended
+        let peak = get_peak(&intervals[4..], &config(), hour() + TimeDelta::minutes(150)).unwrap();
+        assert_eq!((peak.x, peak.y), (hour() + TimeDelta::hours(1), 2.0));
+    }
+
+    #[test]
+    fn hours_outside_windows_not_counted() {
+        let intervals = (0..4).map(|q| interval(q, 1.0)).collect::<Vec<StoredInterval>>();
+        let config = PowerPeak { windows: vec![never()], ..config() };
+
+        assert!(get_peak(&intervals, &config, DateTime::<Utc>::MAX_UTC).is_none());
+    }
+
+    #[test]
+    fn max_of_peaks() {
+        let peak = |y: f64| Some(DataItem { x: hour(), y });
+
+        assert_eq!(max_peak(peak(1.0), peak(2.0)).unwrap().y, 2.0);
+        assert_eq!(max_peak(peak(3.0), peak(2.0)).unwrap().y, 3.0);
+        assert_eq!(max_peak(None, peak(2.0)).unwrap().y, 2.0);
+        assert!(max_peak(None, None).is_none());
+    }
+
+    #[test]
+    fn projection_continues_current_import() {
+        let intervals = vec![interval(0, 1.0), interval(1, 1.0)];
+        let utc_now = hour() + TimeDelta::minutes(35);
+
+        // 2 kWh so far and 4 kW for the remaining half hour
+        let status = get_peak_status(&intervals, None, -4.0, &config(), utc_now);
+        assert_eq!(status.projected, 4.0);
+        assert!(status.color == TariffColor::Red);
+
+        // Exporting adds nothing for the rest of the hour
+        let status = get_peak_status(&intervals, None, 1.0, &config(), utc_now);
+        assert_eq!(status.projected, 2.0);
+    }
+
+    #[test]
+    fn projection_colors() {
+        let intervals = vec![interval(0, 1.0), interval(1, 1.0)];
+        let utc_now = hour() + TimeDelta::minutes(30);
+        let peak = Some(DataItem { x: hour() - TimeDelta::days(1), y: 5.0 });
+        let status = |grid_power: f64, config: &PowerPeak| get_peak_status(&intervals, peak.clone(), grid_power, config, utc_now).color;
+
+        assert!(status(-5.2, &config()) == TariffColor::Yellow);
+        assert!(status(-6.1, &config()) == TariffColor::Red);
+        assert!(status(-2.0, &config()) == TariffColor::Green);
+        assert!(status(-6.1, &PowerPeak::default()) == TariffColor::Green);
+        assert!(status(-6.1, &PowerPeak { windows: vec![never()], ..config() }) == TariffColor::Green);
+    }
+
+    #[test]
+    fn projection_below_min_power_is_green() {
+        let status = get_peak_status(&[], None, -1.0, &config(), hour());
+
+        assert_eq!(status.projected, 1.0);
+        assert!(status.color == TariffColor::Green);
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::manager_mygrid::models::{Block, BlockType};
+use crate::models::DataItem;
+
+/// Max power (kW) and duration (minutes) accepted for an appliance profile
+const MAX_POWER: f64 = 50.0;
+const MAX_DURATION: u32 = 24 * 60;
+
+/// An appliance profile, i.e. how much power it draws and for how long
+///
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Appliance {
+    pub name: String,
+    pub power: f64,
+    pub duration: u32,
+}
+
+/// What a search for the best time to run an appliance is based on
+///
+#[derive(Default)]
+pub struct PlanningData {
+    pub tariffs_buy: Vec<DataItem<f64>>,
+    pub tariffs_sell: HashMap<DateTime<Utc>, f64>,
+    pub charge_blocks: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    pub surplus: HashMap<DateTime<Utc>, f64>,
+    pub time_delta: TimeDelta,
+}
+
+impl PlanningData {
+    /// Returns planning data from tariffs, schedule and MyGrid's production and load estimates.
+    /// The solar surplus is the estimated production less the estimated load per hour
+    ///
+    /// # Arguments
+    ///
+    /// * 'tariffs_buy' - buy tariffs per quarter for the days to search, in time order
+    /// * 'tariffs_sell' - sell tariffs per quarter
+    /// * 'schedule' - the MyGrid schedule
+    /// * 'prod' - estimated production (kW)
+    /// * 'load' - estimated load (kW)
+    /// * 'time_delta' - any configured time delta
+    pub fn new(tariffs_buy: Vec<DataItem<f64>>, tariffs_sell: HashMap<DateTime<Utc>, f64>, schedule: &[Block], prod: &[DataItem<f64>], load: &[DataItem<f64>], time_delta: TimeDelta) -> Self {
+        let charge_blocks = schedule
+            .iter()
+            .filter(|b| b.block_type == BlockType::Charge)
+            .map(|b| (b.start_time, b.end_time))
+            .collect();
+
+        let load = load.iter().map(|l| (l.x, l.y)).collect::<HashMap<DateTime<Utc>, f64>>();
+        let surplus = prod
+            .iter()
+            .map(|p| (p.x, (p.y - load.get(&p.x).copied().unwrap_or(0.0)).max(0.0)))
+            .collect();
+
+        Self { tariffs_buy, tariffs_sell, charge_blocks, surplus, time_delta }
+    }
+
+    /// Returns utc now with the time delta applied
+    ///
+    pub fn utc_now(&self) -> DateTime<Utc> {
+        Utc::now() - self.time_delta
+    }
+}
+
+/// The cheapest window found to run an appliance in
+///
+#[derive(Serialize)]
+pub struct Recommendation {
+    pub appliance: String,
+    pub power: f64,
+    pub duration: u32,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub start: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub end: DateTime<Utc>,
+    pub cost: f64,
+    pub solar_kwh: f64,
+    pub grid_kwh: f64,
+    pub cost_now: Option<f64>,
+}
+
+/// Expected outcome of running an appliance in a given window
+///
+struct Window {
+    start: DateTime<Utc>,
+    cost: f64,
+    solar_kwh: f64,
+    grid_kwh: f64,
+    overlaps_charge: bool,
+}
+
+/// Searches the known tariffs for the cheapest window to run an appliance in, starting at
+/// the next quarter at the earliest.
+///
+/// Per quarter, energy covered by the estimated solar surplus is valued at the sell tariff,
+/// since that's what exporting it would have earned, and the rest at the buy tariff. Windows
+/// overlapping a scheduled charge block are only chosen if no other window fits, since the
+/// battery then already draws from the grid.
+///
+/// # Arguments
+///
+/// * 'appliance' - the appliance profile
+/// * 'data' - tariffs, schedule and production estimates to search
+/// * 'utc_now' - 'now' according to the Utc timezone
+pub fn recommend(appliance: &Appliance, data: &PlanningData, utc_now: DateTime<Utc>) -> Result<Recommendation, RecommenderError> {
+    if !(appliance.power > 0.0 && appliance.power <= MAX_POWER) || appliance.duration == 0 || appliance.duration > MAX_DURATION {
+        return Err(RecommenderError::ProfileError(format!("{} kW for {} minutes", appliance.power, appliance.duration)));
+    }
+
+    let current_quarter = utc_now.duration_trunc(TimeDelta::minutes(15))
+        .map_err(|e| RecommenderError::ProfileError(e.to_string()))?;
+    let quarters = appliance.duration.div_ceil(15) as usize;
+
+    let windows = data.tariffs_buy
+        .windows(quarters)
+        .filter(|w| w[0].x >= current_quarter && is_contiguous(w))
+        .map(|w| evaluate(appliance, data, w))
+        .collect::<Vec<Window>>();
+
+    let best = windows
+        .iter()
+        .filter(|w| !w.overlaps_charge && w.start > current_quarter)
+        .min_by(|a, b| a.cost.total_cmp(&b.cost))
+        .or_else(|| windows.iter().filter(|w| w.start > current_quarter).min_by(|a, b| a.cost.total_cmp(&b.cost)))
+        .ok_or(RecommenderError::NoWindowError(appliance.name.clone()))?;
+
+    let cost_now = windows
+        .iter()
+        .find(|w| w.start == current_quarter)
+        .map(|w| round(w.cost));
+
+    Ok(Recommendation {
+        appliance: appliance.name.clone(),
+        power: appliance.power,
+        duration: appliance.duration,
+        start: best.start,
+        end: best.start + TimeDelta::minutes(appliance.duration as i64),
+        cost: round(best.cost),
+        solar_kwh: round(best.solar_kwh),
+        grid_kwh: round(best.grid_kwh),
+        cost_now,
+    })
+}
+
+/// Returns the expected cost and energy split for running an appliance over the given quarters
+///
+/// # Arguments
+///
+/// * 'appliance' - the appliance profile
+/// * 'data' - tariffs, schedule and production estimates
+/// * 'quarters' - buy tariffs for the quarters the appliance runs in
+fn evaluate(appliance: &Appliance, data: &PlanningData, quarters: &[DataItem<f64>]) -> Window {
+    let start = quarters[0].x;
+    let end = start + TimeDelta::minutes(appliance.duration as i64);
+    let mut window = Window { start, cost: 0.0, solar_kwh: 0.0, grid_kwh: 0.0, overlaps_charge: false };
+
+    for quarter in quarters {
+        let minutes = (end - quarter.x).num_minutes().min(15) as f64;
+        let hours = minutes / 60.0;
+        let energy = appliance.power * hours;
+
+        let surplus = quarter.x.duration_trunc(TimeDelta::hours(1)).ok()
+            .and_then(|hour| data.surplus.get(&hour))
+            .copied()
+            .unwrap_or(0.0);
+        let solar = energy.min(surplus * hours);
+        let grid = energy - solar;
+        let sell = data.tariffs_sell.get(&quarter.x).copied().unwrap_or(0.0);
+
+        window.cost += solar * sell + grid * quarter.y;
+        window.solar_kwh += solar;
+        window.grid_kwh += grid;
+    }
+
+    window.overlaps_charge = data.charge_blocks
+        .iter()
+        .any(|(block_start, block_end)| *block_start < end && *block_end > start);
+
+    window
+}
+
+/// Returns whether the quarters follow each other without gaps
+///
+/// # Arguments
+///
+/// * 'quarters' - quarters in time order
+fn is_contiguous(quarters: &[DataItem<f64>]) -> bool {
+    quarters.windows(2).all(|q| q[1].x - q[0].x == TimeDelta::minutes(15))
+}
+
+/// Rounds to two decimals
+///
+/// # Arguments
+///
+/// * 'value' - the value to round
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[derive(Debug, Error)]
+pub enum RecommenderError {
+    #[error("ProfileError: {0}")]
+    ProfileError(String),
+    #[error("NoWindowError: {0}")]
+    NoWindowError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 22, 10, 0, 0).unwrap()
+    }
+
+    fn quarter(i: i64) -> DateTime<Utc> {
+        start() + TimeDelta::minutes(15 * i)
+    }
+
+    fn data(prices: &[f64]) -> PlanningData {
+        PlanningData {
+            tariffs_buy: prices.iter().enumerate().map(|(i, &y)| DataItem { x: quarter(i as i64), y }).collect(),
+            ..PlanningData::default()
+        }
+    }
+
+    fn appliance(power: f64, duration: u32) -> Appliance {
+        Appliance { name: "dishwasher".to_string(), power, duration }
+    }
+
+    fn now() -> DateTime<Utc> {
+        start() + TimeDelta::minutes(1)
+    }
+
+    #[test]
+    fn invalid_profiles_rejected() {
+        let data = data(&[1.0; 8]);
+
+        for appliance in [appliance(0.0, 30), appliance(60.0, 30), appliance(2.0, 0), appliance(2.0, 25 * 60)] {
+            assert!(matches!(recommend(&appliance, &data, now()), Err(RecommenderError::ProfileError(_))));
+        }
+    }
+
+    #[test]
+    fn cheapest_window_recommended() {
+        let data = data(&[5.0, 5.0, 1.0, 1.0, 5.0, 5.0]);
+
+        let recommendation = recommend(&appliance(2.0, 30), &data, now()).unwrap();
+
+        assert_eq!(recommendation.start, quarter(2));
+        assert_eq!(recommendation.end, quarter(4));
+        assert_eq!(recommendation.cost, 1.0);
+        assert_eq!(recommendation.grid_kwh, 1.0);
+        assert_eq!(recommendation.cost_now, Some(5.0));
+    }
+
+    #[test]
+    fn current_quarter_not_recommended() {
+        let data = data(&[1.0, 1.0, 5.0, 5.0]);
+
+        let recommendation = recommend(&appliance(2.0, 30), &data, now()).unwrap();
+
+        assert_eq!(recommendation.start, quarter(1));
+        assert_eq!(recommendation.cost, 3.0);
+        assert_eq!(recommendation.cost_now, Some(1.0));
+    }
+
+    #[test]
+    fn partial_last_quarter_counts_remaining_minutes() {
+        let data = data(&[5.0, 1.0, 1.0, 5.0]);
+
+        // 2 kW for 15 + 5 minutes
+        let recommendation = recommend(&appliance(2.0, 20), &data, now()).unwrap();
+
+        assert_eq!(recommendation.start, quarter(1));
+        assert_eq!(recommendation.grid_kwh, 0.67);
+    }
+
+    #[test]
+    fn charge_blocks_avoided_unless_nothing_else_fits() {
+        let mut data = data(&[5.0, 1.0, 2.0, 3.0]);
+        data.charge_blocks = vec![(quarter(1), quarter(2))];
+
+        let recommendation = recommend(&appliance(2.0, 15), &data, now()).unwrap();
+        assert_eq!(recommendation.start, quarter(2));
+
+        data.charge_blocks = vec![(quarter(0), quarter(4))];
+        let recommendation = recommend(&appliance(2.0, 15), &data, now()).unwrap();
+        assert_eq!(recommendation.start, quarter(1));
+    }
+
+    #[test]
+    fn solar_surplus_valued_at_sell_tariff() {
+        let mut data = data(&[5.0, 1.0, 2.0, 3.0, 4.0, 4.0, 4.0, 4.0]);
+        data.surplus = HashMap::from([(quarter(4), 4.0)]);
+        data.tariffs_sell = (0..8).map(|i| (quarter(i), 0.5)).collect();
+
+        let recommendation = recommend(&appliance(2.0, 15), &data, now()).unwrap();
+
+        assert_eq!(recommendation.start, quarter(4));
+        assert_eq!(recommendation.solar_kwh, 0.5);
+        assert_eq!(recommendation.grid_kwh, 0.0);
+        assert_eq!(recommendation.cost, 0.25);
+    }
+
+    #[test]
+    fn windows_across_missing_tariffs_skipped() {
+        let mut data = data(&[5.0, 1.0]);
+        data.tariffs_buy.push(DataItem { x: quarter(3), y: 1.0 });
+
+        let result = recommend(&appliance(2.0, 30), &data, now());
+
+        assert!(matches!(result, Err(RecommenderError::NoWindowError(_))));
+    }
+
+    #[test]
+    fn surplus_is_production_less_load() {
+        let prod = vec![DataItem { x: quarter(0), y: 3.0 }, DataItem { x: quarter(4), y: 1.0 }];
+        let load = vec![DataItem { x: quarter(0), y: 1.0 }, DataItem { x: quarter(4), y: 2.0 }];
+
+        let data = PlanningData::new(Vec::new(), HashMap::new(), &[], &prod, &load, TimeDelta::zero());
+
+        assert_eq!(data.surplus.get(&quarter(0)), Some(&2.0));
+        assert_eq!(data.surplus.get(&quarter(4)), Some(&0.0));
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+use crate::export::ExportRow;
+
+/// What a day actually cost compared with what it would have cost without battery and solar,
+/// and with solar only. Costs are net of any revenue from exported energy
+///
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct Savings {
+    pub actual_cost: f64,
+    pub grid_only_cost: f64,
+    pub solar_only_cost: f64,
+    pub saved_vs_grid_only: f64,
+    pub saved_vs_solar_only: f64,
+}
+
+/// Calculates realised savings from actual energy intervals.
+///
+/// Without battery and solar all load is bought at the interval's buy tariff. With solar only,
+/// load is covered by production within each interval, any shortfall is bought and any surplus
+/// sold at the interval's tariffs. The actual cost is the bought energy less the sold.
+///
+/// # Arguments
+///
+/// * 'rows' - the day's intervals with production and load
+pub fn get_savings(rows: &[ExportRow]) -> Savings {
+    let (actual_cost, grid_only_cost, solar_only_cost) = rows
+        .iter()
+        .fold((0.0, 0.0, 0.0), |acc, row| {
+            let net_load = row.load_kwh - row.production_kwh;
+            let solar_only = if net_load > 0.0 {
+                net_load * row.tariff_buy
+            } else {
+                net_load * row.tariff_sell
+            };
+
+            (acc.0 + row.cost - row.revenue, acc.1 + row.load_kwh * row.tariff_buy, acc.2 + solar_only)
+        });
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+
+    Savings {
+        actual_cost: round(actual_cost),
+        grid_only_cost: round(grid_only_cost),
+        solar_only_cost: round(solar_only_cost),
+        saved_vs_grid_only: round(grid_only_cost - actual_cost),
+        saved_vs_solar_only: round(solar_only_cost - actual_cost),
+    }
+}
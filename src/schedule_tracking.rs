@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::export::ExportRow;
+use crate::manager_mygrid::models::Block;
+use crate::models::DataItem;
+
+/// Max deviation in SoC percentage points at the end of a block for it to count as followed
+const MAX_FOLLOWED_DEVIATION: i64 = 5;
+
+/// What actually happened during a schedule block
+///
+#[derive(Serialize, Clone)]
+pub struct BlockActual {
+    pub charged_kwh: f64,
+    pub discharged_kwh: f64,
+    pub grid_cost: f64,
+    pub soc_deviation: Option<i64>,
+}
+
+/// How well the schedule was followed during a day, counting only blocks that have ended
+///
+#[derive(Serialize, Clone, Default)]
+pub struct ScheduleSummary {
+    pub blocks_ended: usize,
+    pub blocks_followed: usize,
+    pub planned_cost: f64,
+    pub actual_cost: f64,
+    pub mean_abs_soc_deviation: Option<f64>,
+}
+
+/// Sets the actual outcome on each block and returns a summary for the blocks that have ended.
+///
+/// Energy into and out of the battery is derived per interval from the energy balance, i.e.
+/// production plus import less load and export. The SoC deviation is the last SoC within the
+/// block compared with the planned SoC at that time, where the plan is a straight line from
+/// 'soc_in' to 'soc_out'
+///
+/// # Arguments
+///
+/// * 'schedule' - the schedule blocks to track
+/// * 'rows' - 15-minute intervals with energy and cost
+/// * 'soc_history' - SoC history
+/// * 'utc_now' - blocks ending before this are included in the summary
+pub fn track_schedule(schedule: &mut [Block], rows: &[ExportRow], soc_history: &[DataItem<u8>], utc_now: DateTime<Utc>) -> ScheduleSummary {
+    let mut summary = ScheduleSummary::default();
+    let mut deviations: Vec<i64> = Vec::new();
+
+    for block in schedule.iter_mut() {
+        let in_block = rows
+            .iter()
+            .filter(|r| r.from >= block.start_time && r.to <= block.end_time)
+            .collect::<Vec<&ExportRow>>();
+
+        if in_block.is_empty() {
+            block.actual = None;
+            continue;
+        }
+
+        let (charged_kwh, discharged_kwh, grid_cost) = in_block
+            .iter()
+            .fold((0.0, 0.0, 0.0), |acc, r| {
+                let battery = r.production_kwh + r.imported_kwh - r.load_kwh - r.exported_kwh;
+                (acc.0 + battery.max(0.0), acc.1 + (-battery).max(0.0), acc.2 + r.cost - r.revenue)
+            });
+
+        let soc_deviation = soc_history
+            .iter()
+            .rfind(|d| d.x >= block.start_time && d.x <= block.end_time)
+            .map(|d| d.y as i64 - planned_soc(block, d.x).round() as i64);
+
+        let round = |v: f64| (v * 100.0).round() / 100.0;
+        block.actual = Some(BlockActual {
+            charged_kwh: round(charged_kwh),
+            discharged_kwh: round(discharged_kwh),
+            grid_cost: round(grid_cost),
+            soc_deviation,
+        });
+
+        if block.end_time <= utc_now {
+            summary.blocks_ended += 1;
+            summary.planned_cost += block.planned_cost;
+            summary.actual_cost += grid_cost;
+            if let Some(deviation) = soc_deviation {
+                deviations.push(deviation);
+                if deviation.abs() <= MAX_FOLLOWED_DEVIATION {
+                    summary.blocks_followed += 1;
+                }
+            }
+        }
+    }
+
+    summary.planned_cost = (summary.planned_cost * 100.0).round() / 100.0;
+    summary.actual_cost = (summary.actual_cost * 100.0).round() / 100.0;
+    if !deviations.is_empty() {
+        let mean = deviations.iter().map(|d| d.abs() as f64).sum::<f64>() / deviations.len() as f64;
+        summary.mean_abs_soc_deviation = Some((mean * 10.0).round() / 10.0);
+    }
+
+    summary
+}
+
+/// Returns the planned SoC at the given time within a block, where the plan is a straight
+/// line from 'soc_in' to 'soc_out'
+///
+/// # Arguments
+///
+/// * 'block' - the schedule block
+/// * 'time' - time within the block
+pub fn planned_soc(block: &Block, time: DateTime<Utc>) -> f64 {
+    let length = (block.end_time - block.start_time).num_seconds() as f64;
+    let elapsed = (time - block.start_time).num_seconds() as f64;
+    let share = if length > 0.0 { (elapsed / length).clamp(0.0, 1.0) } else { 1.0 };
+
+    block.soc_in as f64 + (block.soc_out as f64 - block.soc_in as f64) * share
+}
@@ -0,0 +1,362 @@
+use std::fmt::{Debug, Display};
+use std::time::Duration;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeDelta, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+use crate::metrics::MetricsText;
+
+/// Max wait between retries of a failing source, in minutes
+const MAX_BACKOFF_MINUTES: i64 = 30;
+
+/// Local hour at which day-ahead prices for the next day are expected to be published
+const DAY_AHEAD_PUBLISH_HOUR: u32 = 13;
+
+/// First wait between polls for day-ahead prices not yet published, in minutes
+const DAY_AHEAD_POLL_MINUTES: i64 = 5;
+
+/// Max wait between polls for day-ahead prices not yet published, in minutes
+const MAX_DAY_AHEAD_POLL_MINUTES: i64 = 60;
+
+/// Name and time of last success for a source
+pub type DataSource = (&'static str, Option<DateTime<Utc>>);
+
+/// Status of all upstream sources the dispatcher gets data from
+///
+#[derive(Serialize)]
+pub struct Sources {
+    pub inverter: SourceStatus,
+    pub weather: SourceStatus,
+    pub nordpool: SourceStatus,
+    pub mygrid: SourceStatus,
+}
+
+impl Sources {
+    /// Returns a new instance where no source has been called yet
+    ///
+    pub fn new() -> Self {
+        Self {
+            inverter: SourceStatus::new("inverter"),
+            weather: SourceStatus::new("weather"),
+            nordpool: SourceStatus::new("nordpool"),
+            mygrid: SourceStatus::new("mygrid"),
+        }
+    }
+
+    /// Returns name and time of last success for the sources dashboard data depends on.
+    /// The inverter is only included while live data is polled, since it isn't called otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * 'live' - whether live data is polled
+    pub fn data_sources(&self, live: bool) -> Vec<DataSource> {
+        let mut sources = vec![(self.nordpool.name, self.nordpool.last_success)];
+        if live {
+            sources.push((self.inverter.name, self.inverter.last_success));
+        }
+
+        sources
+    }
+
+    /// Adds request, error and latency metrics per source
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - metrics builder to add to
+    pub fn write_metrics(&self, text: &mut MetricsText) {
+        let sources = [&self.inverter, &self.weather, &self.nordpool, &self.mygrid];
+        let samples = |value: fn(&SourceStatus) -> f64| sources
+            .iter()
+            .map(|s| (format!("source=\"{}\"", s.name), value(s)))
+            .collect::<Vec<(String, f64)>>();
+
+        text.metric("mygrid_dash_upstream_requests_total", "counter", "Update attempts per upstream source", &samples(|s| s.requests as f64));
+        text.metric("mygrid_dash_upstream_errors_total", "counter", "Failed update attempts per upstream source", &samples(|s| s.errors as f64));
+        text.metric("mygrid_dash_upstream_stale", "gauge", "Whether data from the upstream source is stale (1) or not (0)", &samples(|s| if s.stale { 1.0 } else { 0.0 }));
+        text.metric::<String>("mygrid_dash_upstream_duration_seconds", "summary", "Time spent updating from the upstream source", &[]);
+        for (labels, value) in samples(|s| s.duration.as_secs_f64()) {
+            text.sample("mygrid_dash_upstream_duration_seconds_sum", &labels, value);
+        }
+        for (labels, value) in samples(|s| s.requests as f64) {
+            text.sample("mygrid_dash_upstream_duration_seconds_count", &labels, value);
+        }
+    }
+}
+
+/// Keeps track of successes and failures for one upstream source.
+/// A source is stale until it first succeeds and from any failure until it succeeds again,
+/// and a failing source is retried with an exponential backoff while the last good data is kept
+///
+#[derive(Serialize)]
+pub struct SourceStatus {
+    #[serde(skip)]
+    name: &'static str,
+    #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    pub stale_since: Option<DateTime<Utc>>,
+    pub stale: bool,
+    #[serde(skip)]
+    failures: u32,
+    #[serde(skip)]
+    next_attempt: DateTime<Utc>,
+    #[serde(skip)]
+    requests: u64,
+    #[serde(skip)]
+    errors: u64,
+    #[serde(skip)]
+    duration: Duration,
+}
+
+impl SourceStatus {
+    /// Returns a new instance of SourceStatus
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - name of the source, used in logs
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            last_success: None,
+            last_error: None,
+            stale_since: None,
+            stale: true,
+            failures: 0,
+            next_attempt: DateTime::<Utc>::MIN_UTC,
+            requests: 0,
+            errors: 0,
+            duration: Duration::ZERO,
+        }
+    }
+
+    /// Returns true if the source may be called, i.e. it is not backing off after a failure
+    ///
+    pub fn is_due(&self) -> bool {
+        Utc::now() >= self.next_attempt
+    }
+
+    /// Records the result from calling the source and returns the value if successful.
+    /// Failures are logged and push the next attempt further away, up to a max wait
+    ///
+    /// # Arguments
+    ///
+    /// * 'result' - the result from calling the source
+    /// * 'elapsed' - time spent calling the source
+    pub fn record<T, E: Debug + Display>(&mut self, result: Result<T, E>, elapsed: Duration) -> Option<T> {
+        let now = Utc::now();
+        self.requests += 1;
+        self.duration += elapsed;
+
+        match result {
+            Ok(value) => {
+                if let Some(stale_since) = self.stale_since {
+                    info!("{} recovered, stale since {}", self.name, stale_since);
+                }
+                self.last_success = Some(now);
+                self.stale_since = None;
+                self.stale = false;
+                self.failures = 0;
+                self.next_attempt = now;
+
+                Some(value)
+            },
+            Err(e) => {
+                let backoff = 2i64.saturating_pow(self.failures).min(MAX_BACKOFF_MINUTES);
+                warn!("{} failed, retrying in {} minutes: {:?}", self.name, backoff, e);
+
+                self.errors += 1;
+                self.last_error = Some(format!("{:#}", e));
+                self.stale_since.get_or_insert(now);
+                self.stale = true;
+                self.failures = self.failures.saturating_add(1);
+                self.next_attempt = now + TimeDelta::minutes(backoff);
+
+                None
+            },
+        }
+    }
+}
+
+/// Schedules polls for the next day's day-ahead prices. Nothing is polled before the prices
+/// are expected to be published, and polls finding the prices not yet published, or only
+/// partly so, back off up to a max wait
+///
+pub struct DayAheadPoll {
+    date: Option<NaiveDate>,
+    polls: u32,
+    next_attempt: DateTime<Utc>,
+}
+
+impl DayAheadPoll {
+    /// Returns a new instance of DayAheadPoll
+    ///
+    pub fn new() -> Self {
+        Self {
+            date: None,
+            polls: 0,
+            next_attempt: DateTime::<Utc>::MIN_UTC,
+        }
+    }
+
+    /// Returns true if prices for the given date may be polled
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the date prices are for
+    /// * 'utc_now' - 'now' according to the Utc timezone
+    pub fn is_due(&self, date: NaiveDate, utc_now: DateTime<Utc>) -> bool {
+        if self.date == Some(date) {
+            utc_now >= self.next_attempt
+        } else {
+            utc_now >= publish_time(date)
+        }
+    }
+
+    /// Records the outcome of a poll, backing off if the prices weren't there
+    ///
+    /// # Arguments
+    ///
+    /// * 'date' - the date prices are for
+    /// * 'published' - whether the prices were there
+    /// * 'utc_now' - 'now' according to the Utc timezone
+    pub fn record(&mut self, date: NaiveDate, published: bool, utc_now: DateTime<Utc>) {
+        if self.date != Some(date) {
+            self.date = Some(date);
+            self.polls = 0;
+        }
+
+        if published {
+            self.next_attempt = utc_now;
+        } else {
+            let backoff = (DAY_AHEAD_POLL_MINUTES * 2i64.saturating_pow(self.polls)).min(MAX_DAY_AHEAD_POLL_MINUTES);
+            info!("day-ahead prices for {} not available, polling again in {} minutes", date, backoff);
+
+            self.polls = self.polls.saturating_add(1);
+            self.next_attempt = utc_now + TimeDelta::minutes(backoff);
+        }
+    }
+}
+
+/// Returns when day-ahead prices for the given date are expected to be published
+///
+/// # Arguments
+///
+/// * 'date' - the date prices are for
+fn publish_time(date: NaiveDate) -> DateTime<Utc> {
+    date.checked_sub_days(Days::new(1))
+        .and_then(|d| d.and_time(NaiveTime::MIN).and_local_timezone(Local).earliest())
+        .map_or(DateTime::<Utc>::MIN_UTC, |d| (d + TimeDelta::hours(DAY_AHEAD_PUBLISH_HOUR as i64)).to_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(status: &mut SourceStatus) -> i64 {
+        let before = Utc::now();
+        status.record::<(), &str>(Err("unreachable"), Duration::ZERO);
+
+        ((status.next_attempt - before).num_seconds() as f64 / 60.0).round() as i64
+    }
+
+    #[test]
+    fn new_source_is_stale_and_due() {
+        let status = SourceStatus::new("inverter");
+
+        assert!(status.stale);
+        assert!(status.last_success.is_none());
+        assert!(status.is_due());
+    }
+
+    #[test]
+    fn failures_back_off_exponentially_up_to_max() {
+        let mut status = SourceStatus::new("inverter");
+
+        let backoffs = (0..8).map(|_| fail(&mut status)).collect::<Vec<i64>>();
+
+        assert_eq!(backoffs, vec![1, 2, 4, 8, 16, 30, 30, 30]);
+        assert!(!status.is_due());
+        assert_eq!(status.errors, 8);
+        assert_eq!(status.last_error.as_deref(), Some("unreachable"));
+    }
+
+    #[test]
+    fn stale_since_first_failure_until_success() {
+        let mut status = SourceStatus::new("weather");
+        assert_eq!(status.record::<u8, &str>(Ok(1), Duration::ZERO), Some(1));
+        assert!(!status.stale);
+
+        fail(&mut status);
+        let stale_since = status.stale_since;
+        fail(&mut status);
+        assert!(status.stale);
+        assert!(stale_since.is_some());
+        assert_eq!(status.stale_since, stale_since);
+
+        assert_eq!(status.record::<u8, &str>(Ok(2), Duration::ZERO), Some(2));
+        assert!(!status.stale);
+        assert!(status.stale_since.is_none());
+        assert!(status.is_due());
+
+        // Backoff starts over after a success
+        assert_eq!(fail(&mut status), 1);
+        assert_eq!(status.requests, 5);
+    }
+
+    #[test]
+    fn inverter_only_a_data_source_while_live() {
+        let sources = Sources::new();
+
+        let names = |live: bool| sources.data_sources(live).iter().map(|(name, _)| *name).collect::<Vec<&str>>();
+
+        assert_eq!(names(false), vec!["nordpool"]);
+        assert_eq!(names(true), vec!["nordpool", "inverter"]);
+    }
+
+    #[test]
+    fn day_ahead_not_polled_before_publication() {
+        let poll = DayAheadPoll::new();
+        let date = NaiveDate::from_ymd_opt(2025, 10, 23).unwrap();
+        let published = publish_time(date);
+
+        assert_eq!(published.with_timezone(&Local).date_naive(), NaiveDate::from_ymd_opt(2025, 10, 22).unwrap());
+        assert!(!poll.is_due(date, published - TimeDelta::minutes(1)));
+        assert!(poll.is_due(date, published));
+    }
+
+    #[test]
+    fn day_ahead_polls_back_off_up_to_max() {
+        let mut poll = DayAheadPoll::new();
+        let date = NaiveDate::from_ymd_opt(2025, 10, 23).unwrap();
+        let mut utc_now = publish_time(date);
+
+        let mut backoffs = Vec::new();
+        for _ in 0..6 {
+            poll.record(date, false, utc_now);
+            backoffs.push((poll.next_attempt - utc_now).num_minutes());
+            assert!(!poll.is_due(date, utc_now));
+            utc_now = poll.next_attempt;
+            assert!(poll.is_due(date, utc_now));
+        }
+
+        assert_eq!(backoffs, vec![5, 10, 20, 40, 60, 60]);
+    }
+
+    #[test]
+    fn day_ahead_backoff_starts_over() {
+        let mut poll = DayAheadPoll::new();
+        let date = NaiveDate::from_ymd_opt(2025, 10, 23).unwrap();
+        let utc_now = publish_time(date);
+
+        poll.record(date, false, utc_now);
+        poll.record(date, false, utc_now);
+        poll.record(date, true, utc_now);
+        assert!(poll.is_due(date, utc_now));
+
+        // A new date is polled from its own publication, with the backoff reset
+        let next_date = date.succ_opt().unwrap();
+        assert!(!poll.is_due(next_date, utc_now));
+        poll.record(next_date, false, publish_time(next_date));
+        assert_eq!((poll.next_attempt - publish_time(next_date)).num_minutes(), 5);
+    }
+}
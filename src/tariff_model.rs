@@ -0,0 +1,206 @@
+use chrono::{DateTime, Datelike, Local, Timelike, Utc};
+use serde::Serialize;
+use crate::initialization::{FeeMode, GenericTariff, LocalTariffFees, SwedishTariff, TariffModelConfig, TimeOfUseFee, TimeWindow};
+use crate::models::TariffFees;
+
+/// Swedish VAT rate
+const SWEDISH_VAT: f64 = 0.25;
+
+/// Where the tariff fees in effect come from
+///
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeSource {
+    MyGrid,
+    Config,
+}
+
+/// Turns spot prices into the prices actually paid when buying and received when selling
+///
+pub trait TariffModel: Send + Sync {
+    /// Returns buy and sell prices (currency/kWh) for one spot price
+    ///
+    /// # Arguments
+    ///
+    /// * 'fees' - fees as given by MyGrid for the day
+    /// * 'day_avg' - average spot price for the day (currency/kWh)
+    /// * 'spot' - spot price (currency/kWh)
+    /// * 'delivery_start' - start time for the spot
+    fn prices(&self, fees: &TariffFees, day_avg: f64, spot: f64, delivery_start: DateTime<Utc>) -> (f64, f64);
+}
+
+/// Returns the tariff model configured
+///
+/// # Arguments
+///
+/// * 'config' - tariff model configuration
+pub fn get_tariff_model(config: &TariffModelConfig) -> Box<dyn TariffModel> {
+    match config {
+        TariffModelConfig::Swedish(tariff) => Box::new(SwedishModel { tariff: tariff.clone() }),
+        TariffModelConfig::Generic(tariff) => Box::new(GenericModel { tariff: tariff.clone() }),
+    }
+}
+
+/// Returns the tariff fees to use together with where they come from. Fees from the
+/// configuration are used if set to override, otherwise MyGrid's fees are used and the
+/// configured fees only when MyGrid's aren't known
+///
+/// # Arguments
+///
+/// * 'mygrid' - fees from MyGrid base data, if known
+/// * 'local' - fees from the configuration, if any
+pub fn select_tariff_fees(mygrid: Option<&TariffFees>, local: Option<&LocalTariffFees>) -> Option<(TariffFees, FeeSource)> {
+    match (mygrid, local) {
+        (_, Some(local)) if local.mode == FeeMode::Override => Some((local.fees.clone(), FeeSource::Config)),
+        (Some(mygrid), _) => Some((mygrid.clone(), FeeSource::MyGrid)),
+        (None, Some(local)) => Some((local.fees.clone(), FeeSource::Config)),
+        (None, None) => None,
+    }
+}
+
+/// Swedish rules, with energy tax, grid and trade fees as given by MyGrid, and 25% VAT on
+/// everything bought. Selling is spot plus the production price, without VAT.
+///
+/// A configured time-of-use variable fee replaces MyGrid's flat variable fee for the times it
+/// covers
+///
+pub struct SwedishModel {
+    tariff: SwedishTariff,
+}
+
+impl TariffModel for SwedishModel {
+    fn prices(&self, fees: &TariffFees, day_avg: f64, spot: f64, delivery_start: DateTime<Utc>) -> (f64, f64) {
+        let variable_fee = time_of_use_fee(&self.tariff.variable_fees, delivery_start).unwrap_or(fees.variable_fee);
+        let grid_fees = (variable_fee + fees.energy_tax) / 100.0 + fees.spot_fee_percentage / 100.0 * day_avg;
+        let trade_fees = (fees.swedish_power_grid + fees.balance_responsibility + fees.electric_certificate +
+            fees.guarantees_of_origin + fees.fixed) / 100.0 + spot;
+
+        let buy = (grid_fees + trade_fees) * (1.0 + SWEDISH_VAT);
+        let sell = fees.production_price / 100.0 + spot;
+
+        (buy, sell)
+    }
+}
+
+/// Configuration driven rules for sites outside Sweden. MyGrid's fees are not used, all fees
+/// come from the configuration
+///
+pub struct GenericModel {
+    tariff: GenericTariff,
+}
+
+impl TariffModel for GenericModel {
+    fn prices(&self, _: &TariffFees, _: f64, spot: f64, delivery_start: DateTime<Utc>) -> (f64, f64) {
+        let t = &self.tariff;
+        let vat = 1.0 + t.vat / 100.0;
+
+        let energy = spot * (1.0 + t.percentage_markup / 100.0) + t.fixed_markup / 100.0;
+        let grid_fee = time_of_use_fee(&t.grid_fees, delivery_start).unwrap_or(t.grid_fee);
+        let buy = (energy + (grid_fee + t.energy_tax) / 100.0) * vat;
+
+        let sell = spot + t.sell_markup / 100.0;
+        let sell = if t.vat_on_sell { sell * vat } else { sell };
+
+        (buy, sell)
+    }
+}
+
+/// Returns the fee of the first time-of-use fee covering the given time, if any
+///
+/// # Arguments
+///
+/// * 'fees' - time-of-use fees
+/// * 'date_time' - the time to get the fee for
+fn time_of_use_fee(fees: &[TimeOfUseFee], date_time: DateTime<Utc>) -> Option<f64> {
+    fees.iter()
+        .find(|f| in_window(&f.window, date_time))
+        .map(|f| f.fee)
+}
+
+/// Returns whether the given time is within the time window. The window covers local hours from
+/// 'from_hour' up to 'to_hour', wrapping past midnight if 'to_hour' is the smaller, on the listed
+/// weekdays and months, where an empty list means all of them
+///
+/// # Arguments
+///
+/// * 'window' - the time window
+/// * 'date_time' - the time to check
+pub fn in_window(window: &TimeWindow, date_time: DateTime<Utc>) -> bool {
+    let local = date_time.with_timezone(&Local);
+    let hour = local.hour();
+
+    let in_hours = if window.from_hour <= window.to_hour {
+        hour >= window.from_hour && hour < window.to_hour
+    } else {
+        hour >= window.from_hour || hour < window.to_hour
+    };
+
+    in_hours &&
+        (window.weekdays.is_empty() || window.weekdays.contains(&local.weekday())) &&
+        (window.months.is_empty() || window.months.contains(&local.month()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    fn fees(spot_fee_percentage: f64) -> TariffFees {
+        TariffFees {
+            variable_fee: 20.0,
+            spot_fee_percentage,
+            energy_tax: 40.0,
+            swedish_power_grid: 1.0,
+            balance_responsibility: 2.0,
+            electric_certificate: 3.0,
+            guarantees_of_origin: 4.0,
+            fixed: 5.0,
+            production_price: 8.0,
+        }
+    }
+
+    fn variable_fee(from_hour: u32, to_hour: u32, fee: f64) -> TimeOfUseFee {
+        TimeOfUseFee { window: TimeWindow { from_hour, to_hour, weekdays: Vec::new(), months: Vec::new() }, fee }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn swedish_prices_with_mygrid_fees() {
+        let model = SwedishModel { tariff: SwedishTariff::default() };
+        let delivery_start = Utc.with_ymd_and_hms(2025, 10, 22, 10, 0, 0).unwrap();
+
+        let (buy, sell) = model.prices(&fees(0.0), 2.0, 1.0, delivery_start);
+
+        // ((20 + 40) / 100 + (1 + 2 + 3 + 4 + 5) / 100 + 1.0) * 1.25
+        assert_close(buy, 2.1875);
+        // 8 / 100 + 1.0, no VAT on selling
+        assert_close(sell, 1.08);
+    }
+
+    #[test]
+    fn swedish_spot_fee_is_percent_of_day_average() {
+        let model = SwedishModel { tariff: SwedishTariff::default() };
+        let delivery_start = Utc.with_ymd_and_hms(2025, 10, 22, 10, 0, 0).unwrap();
+
+        let (without_fee, _) = model.prices(&fees(0.0), 2.0, 1.0, delivery_start);
+        let (with_fee, sell) = model.prices(&fees(10.0), 2.0, 1.0, delivery_start);
+
+        // 10% of the day average 2.0, with VAT, regardless of the spot price itself
+        assert_close(with_fee - without_fee, 0.25);
+        assert_close(sell, 1.08);
+    }
+
+    #[test]
+    fn swedish_time_of_use_fee_replaces_variable_fee() {
+        let always = SwedishModel { tariff: SwedishTariff { variable_fees: vec![variable_fee(0, 24, 50.0)] } };
+        let never = SwedishModel { tariff: SwedishTariff { variable_fees: vec![variable_fee(0, 0, 50.0)] } };
+        let delivery_start = Utc.with_ymd_and_hms(2025, 10, 22, 10, 0, 0).unwrap();
+
+        // ((50 + 40) / 100 + (1 + 2 + 3 + 4 + 5) / 100 + 1.0) * 1.25
+        assert_close(always.prices(&fees(0.0), 2.0, 1.0, delivery_start).0, 2.5625);
+        assert_close(never.prices(&fees(0.0), 2.0, 1.0, delivery_start).0, 2.1875);
+    }
+}
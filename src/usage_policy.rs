@@ -1,12 +1,136 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
 use std::iter::successors;
 use std::ops::Add;
-use chrono::{DateTime, TimeDelta, Utc};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::Serialize;
+use crate::initialization::{ThresholdMode, UsagePolicy};
 use crate::manager_mygrid::models::{Block, BlockType};
-use crate::models::TariffColor;
+use crate::models::{DataItem, TariffColor};
+use crate::power_peak::PeakStatus;
+use crate::schedule_tracking::planned_soc;
 
+/// Buy tariffs above which the color turns yellow and red respectively
+///
+#[derive(Serialize, Clone, Copy)]
+pub struct Thresholds {
+    pub yellow: f64,
+    pub red: f64,
+}
 
-/// Evaluates and returns usage tariff color
+/// Rules the usage policy is evaluated by
+///
+#[derive(Clone, Copy)]
+pub struct PolicyRules {
+    pub thresholds: Thresholds,
+    pub min_soc: u8,
+    pub hold_grid_threshold: f64,
+    pub lookahead_quarters: u8,
+}
+
+impl PolicyRules {
+    /// Returns rules from the usage policy configuration, where relative thresholds are
+    /// resolved against the given day's buy tariffs
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - usage policy configuration
+    /// * 'day_tariffs' - the day's buy tariffs, if known
+    pub fn new(config: &UsagePolicy, day_tariffs: Option<&Vec<DataItem<f64>>>) -> Self {
+        Self {
+            thresholds: get_thresholds(config, day_tariffs),
+            min_soc: config.min_soc,
+            hold_grid_threshold: config.hold_grid_threshold,
+            lookahead_quarters: config.lookahead_quarters.max(1),
+        }
+    }
+}
+
+/// Returns the tariff thresholds for a day, either the absolute ones from configuration or
+/// percentiles of the day's buy tariffs. Percentiles fall back to the absolute thresholds
+/// when the day's tariffs aren't known
+///
+/// # Arguments
+///
+/// * 'config' - usage policy configuration
+/// * 'day_tariffs' - the day's buy tariffs, if known
+pub fn get_thresholds(config: &UsagePolicy, day_tariffs: Option<&Vec<DataItem<f64>>>) -> Thresholds {
+    let absolute = Thresholds { yellow: config.yellow_threshold, red: config.red_threshold };
+
+    match (config.threshold_mode, day_tariffs) {
+        (ThresholdMode::Percentile, Some(tariffs)) if !tariffs.is_empty() => {
+            let mut prices = tariffs.iter().map(|t| t.y).collect::<Vec<f64>>();
+            prices.sort_by(f64::total_cmp);
+
+            Thresholds {
+                yellow: percentile(&prices, config.yellow_percentile),
+                red: percentile(&prices, config.red_percentile),
+            }
+        },
+        _ => absolute,
+    }
+}
+
+
+/// Why the usage policy got its color
+///
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind")]
+pub enum PolicyReason {
+    /// Use block with SoC below the minimum, the color follows the tariff ahead
+    UseLowSoc { soc: u8, min_soc: u8, tariff_avg: Option<f64> },
+    /// Use block, the color follows the average tariff the battery was charged at
+    UseCharged { charge_avg: f64 },
+    /// Use block without any earlier charge block
+    UseNotCharged,
+    /// Hold block while not importing more than the threshold
+    HoldNotImporting { grid_power: f64 },
+    /// Hold block while importing, the color follows the tariff ahead
+    HoldImporting { grid_power: f64, tariff_avg: Option<f64> },
+    /// Charge block or no block at all, the color follows the tariff ahead
+    Tariff { block_type: Option<BlockType>, tariff_avg: Option<f64> },
+    /// The current hour's import is heading for, or past, the month's power peak
+    PowerPeak { projected: f64, peak: Option<f64> },
+}
+
+/// Implementation of the Display Trait for notifications and logging
+impl fmt::Display for PolicyReason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let price = |p: &Option<f64>| p.map_or("unknown".to_string(), |p| format!("{:.2}", p));
+
+        match self {
+            PolicyReason::UseLowSoc { soc, min_soc, tariff_avg } =>
+                write!(f, "Use block, SoC {}% below {}%, tariff avg {} ahead", soc, min_soc, price(tariff_avg)),
+            PolicyReason::UseCharged { charge_avg } =>
+                write!(f, "Use block, battery charged at avg {:.2}", charge_avg),
+            PolicyReason::UseNotCharged =>
+                write!(f, "Use block, battery not charged from grid"),
+            PolicyReason::HoldNotImporting { grid_power } =>
+                write!(f, "Hold block, grid {:.1} kW", grid_power),
+            PolicyReason::HoldImporting { grid_power, tariff_avg } =>
+                write!(f, "Hold block, importing {:.1} kW, tariff avg {} ahead", grid_power.abs(), price(tariff_avg)),
+            PolicyReason::Tariff { block_type: Some(block_type), tariff_avg } =>
+                write!(f, "{:?} block, tariff avg {} ahead", block_type, price(tariff_avg)),
+            PolicyReason::Tariff { block_type: None, tariff_avg } =>
+                write!(f, "No block, tariff avg {} ahead", price(tariff_avg)),
+            PolicyReason::PowerPeak { projected, peak: Some(peak) } =>
+                write!(f, "Hour import heading for {:.1} kW, month peak {:.1} kW", projected, peak),
+            PolicyReason::PowerPeak { projected, peak: None } =>
+                write!(f, "Hour import heading for {:.1} kW, first peak of month", projected),
+        }
+    }
+}
+
+/// Usage policy color together with the reason for it
+///
+#[derive(Serialize, Clone)]
+pub struct PolicyDecision {
+    pub color: TariffColor,
+    pub reason: PolicyReason,
+}
+
+/// Evaluates and returns usage tariff color and the reason for it
 ///
 /// # Arguments
 /// 
@@ -15,7 +139,8 @@ use crate::models::TariffColor;
 /// * 'schedule' - schedule of the day, used to determine if the battery is discharging or not
 /// * 'tariffs' - hourly buy tariffs
 /// * 'grid_power' - current grid power
-pub fn get_policy(date_time: DateTime<Utc>, soc: u8, schedule: &Vec<Block>, tariffs: &HashMap<DateTime<Utc>, f64>, grid_power: f64) -> TariffColor {
+/// * 'rules' - thresholds and limits to evaluate by
+pub fn get_policy(date_time: DateTime<Utc>, soc: u8, schedule: &Vec<Block>, tariffs: &HashMap<DateTime<Utc>, f64>, grid_power: f64, rules: &PolicyRules) -> PolicyDecision {
 
     // Get current schedule block type
     let current_block_type = schedule
@@ -24,12 +149,16 @@ pub fn get_policy(date_time: DateTime<Utc>, soc: u8, schedule: &Vec<Block>, tari
         .last()
         .map(|b| b.block_type.clone());
 
-    let now_color = tariff_color_now(date_time, tariffs);
+    let tariff_avg = tariff_avg_now(date_time, tariffs, rules);
+    let now_color = cost_to_color(tariff_avg, &rules.thresholds);
 
     match current_block_type {
         Some(BlockType::Use) => {
-            if soc < 20 {
-                now_color
+            if soc < rules.min_soc {
+                PolicyDecision {
+                    color: now_color,
+                    reason: PolicyReason::UseLowSoc { soc, min_soc: rules.min_soc, tariff_avg: tariff_avg.map(round) },
+                }
             } else {
                 let last_charge_time = schedule
                     .iter()
@@ -50,34 +179,99 @@ pub fn get_policy(date_time: DateTime<Utc>, soc: u8, schedule: &Vec<Block>, tari
                     total_price / intervals as f64
                 });
 
-                if charge_price.is_some() {
-                    cost_to_color(charge_price)
+                if let Some(charge_avg) = charge_price {
+                    PolicyDecision {
+                        color: cost_to_color(charge_price, &rules.thresholds),
+                        reason: PolicyReason::UseCharged { charge_avg: round(charge_avg) },
+                    }
                 } else {
-                    TariffColor::Green
+                    PolicyDecision { color: TariffColor::Green, reason: PolicyReason::UseNotCharged }
                 }
             }
         },
         Some(BlockType::Hold) => {
-            if grid_power > -0.2 {
-                TariffColor::Green
+            if grid_power > rules.hold_grid_threshold {
+                PolicyDecision {
+                    color: TariffColor::Green,
+                    reason: PolicyReason::HoldNotImporting { grid_power: round(grid_power) },
+                }
             } else
             {
-                now_color
+                PolicyDecision {
+                    color: now_color,
+                    reason: PolicyReason::HoldImporting { grid_power: round(grid_power), tariff_avg: tariff_avg.map(round) },
+                }
             }
         },
-        _ => now_color,
+        block_type => PolicyDecision {
+            color: now_color,
+            reason: PolicyReason::Tariff { block_type, tariff_avg: tariff_avg.map(round) },
+        },
+    }
+}
+
+/// Returns the decision raised to the power peak warning, if the warning is the more severe
+///
+/// # Arguments
+///
+/// * 'decision' - the usage policy decision
+/// * 'status' - the power peak status for the current hour
+pub fn apply_peak_warning(decision: PolicyDecision, status: &PeakStatus) -> PolicyDecision {
+    if severity(&status.color) > severity(&decision.color) {
+        PolicyDecision {
+            color: status.color.clone(),
+            reason: PolicyReason::PowerPeak { projected: status.projected, peak: status.peak.as_ref().map(|p| p.y) },
+        }
+    } else {
+        decision
     }
 }
 
-/// Returns the tariff color for the given datetime.
-/// The color reflects the average of the nearest future (approx 4 quarters or 1 hour)
+/// Evaluates the usage policy for every quarter with a known tariff from the given quarter on.
+/// Instead of live values the SoC is the planned SoC from the schedule block covering each
+/// quarter, and the grid power is estimated production less estimated load
+///
+/// # Arguments
+///
+/// * 'from' - first quarter to evaluate
+/// * 'schedule' - the schedule, used for block types and planned SoC
+/// * 'tariffs' - buy tariffs per quarter for all days to evaluate
+/// * 'days' - end of each day to evaluate together with the rules for that day, in time order
+/// * 'grid_estimates' - estimated grid power per hour
+/// * 'soc' - SoC to use for quarters not covered by the schedule
+pub fn get_policy_timeline(from: DateTime<Utc>, schedule: &Vec<Block>, tariffs: &HashMap<DateTime<Utc>, f64>, days: &[(DateTime<Utc>, PolicyRules)], grid_estimates: &HashMap<DateTime<Utc>, f64>, soc: u8) -> Vec<DataItem<PolicyDecision>> {
+    let mut quarters = tariffs.keys().filter(|q| **q >= from).copied().collect::<Vec<DateTime<Utc>>>();
+    quarters.sort();
+
+    quarters
+        .into_iter()
+        .filter_map(|quarter| {
+            let (_, rules) = days.iter().find(|(day_end, _)| quarter < *day_end)?;
+
+            let planned_soc = schedule
+                .iter()
+                .find(|b| b.start_time <= quarter && b.end_time > quarter)
+                .map_or(soc, |b| planned_soc(b, quarter).round().clamp(0.0, 100.0) as u8);
+            let grid_power = quarter.duration_trunc(TimeDelta::hours(1)).ok()
+                .and_then(|hour| grid_estimates.get(&hour))
+                .copied()
+                .unwrap_or(0.0);
+
+            Some(DataItem { x: quarter, y: get_policy(quarter, planned_soc, schedule, tariffs, grid_power, rules) })
+        })
+        .collect()
+}
+
+/// Returns the average tariff of the nearest future (by default 4 quarters or 1 hour) from
+/// the given datetime, or None if no tariffs are known for it
 ///
 /// # Arguments
 ///
 /// * 'date_time' - a datetime (quarter) to evaluate
 /// * 'tariffs' - hourly buy tariffs
-fn tariff_color_now(date_time: DateTime<Utc>, tariffs: &HashMap<DateTime<Utc>, f64>) -> TariffColor {
-    let price_sum = (0..4).fold((0.0f64, 0u8), |acc, i| {
+/// * 'rules' - look-ahead window
+fn tariff_avg_now(date_time: DateTime<Utc>, tariffs: &HashMap<DateTime<Utc>, f64>, rules: &PolicyRules) -> Option<f64> {
+    let price_sum = (0..rules.lookahead_quarters).fold((0.0f64, 0u8), |acc, i| {
         let dt = date_time.add(TimeDelta::minutes(i as i64 * 15));
         if let Some(&cost) = tariffs.get(&dt) {
             (acc.0 + cost, acc.1 + 1)
@@ -86,13 +280,11 @@ fn tariff_color_now(date_time: DateTime<Utc>, tariffs: &HashMap<DateTime<Utc>, f
         }
     });
 
-    let price_avg = if price_sum.1 != 0 {
+    if price_sum.1 != 0 {
         Some(price_sum.0 / price_sum.1 as f64)
     } else {
         None
-    };
-
-    cost_to_color(price_avg)
+    }
 }
 
 /// Translates a cost to a color
@@ -101,11 +293,12 @@ fn tariff_color_now(date_time: DateTime<Utc>, tariffs: &HashMap<DateTime<Utc>, f
 /// # Arguments
 /// 
 /// * 'cost' - cost to translate
-fn cost_to_color(cost: Option<f64>) -> TariffColor {
+/// * 'thresholds' - costs above which the color turns yellow and red
+fn cost_to_color(cost: Option<f64>, thresholds: &Thresholds) -> TariffColor {
     if let Some(cost) = cost {
-        if cost > 4.0 {
+        if cost > thresholds.red {
             TariffColor::Red
-        } else if cost > 2.0 {
+        } else if cost > thresholds.yellow {
             TariffColor::Yellow
         } else {
             TariffColor::Green
@@ -113,4 +306,123 @@ fn cost_to_color(cost: Option<f64>) -> TariffColor {
     } else {
         TariffColor::Green
     }
-}
\ No newline at end of file
+}
+
+/// Returns the severity of a color, green being the least severe
+///
+/// # Arguments
+///
+/// * 'color' - the color
+fn severity(color: &TariffColor) -> u8 {
+    match color {
+        TariffColor::Green => 0,
+        TariffColor::Yellow => 1,
+        TariffColor::Red => 2,
+    }
+}
+
+/// Returns the given percentile of sorted values, interpolating between the closest ranks
+///
+/// # Arguments
+///
+/// * 'sorted' - values sorted in ascending order, not empty
+/// * 'percentile' - the percentile (0-100) to return
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// Rounds to two decimals
+///
+/// # Arguments
+///
+/// * 'value' - the value to round
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    fn tariffs(prices: &[f64]) -> Vec<DataItem<f64>> {
+        let day_start = Utc.with_ymd_and_hms(2025, 10, 21, 22, 0, 0).unwrap();
+        prices
+            .iter()
+            .enumerate()
+            .map(|(q, &y)| DataItem { x: day_start + TimeDelta::minutes(15 * q as i64), y })
+            .collect()
+    }
+
+    fn percentile_config() -> UsagePolicy {
+        UsagePolicy { threshold_mode: ThresholdMode::Percentile, ..UsagePolicy::default() }
+    }
+
+    #[test]
+    fn absolute_thresholds_ignore_tariffs() {
+        let thresholds = get_thresholds(&UsagePolicy::default(), Some(&tariffs(&[1.0, 2.0, 3.0])));
+
+        assert_eq!(thresholds.yellow, 2.0);
+        assert_eq!(thresholds.red, 4.0);
+    }
+
+    #[test]
+    fn percentile_thresholds_from_day_tariffs() {
+        // Unsorted on purpose, the 50th and 80th percentiles of 1-5 are 3 and 4.2
+        let thresholds = get_thresholds(&percentile_config(), Some(&tariffs(&[5.0, 1.0, 4.0, 2.0, 3.0])));
+
+        assert_eq!(thresholds.yellow, 3.0);
+        assert!((thresholds.red - 4.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn percentile_thresholds_fall_back_to_absolute() {
+        let config = percentile_config();
+
+        let thresholds = get_thresholds(&config, None);
+        assert_eq!((thresholds.yellow, thresholds.red), (2.0, 4.0));
+
+        let thresholds = get_thresholds(&config, Some(&Vec::new()));
+        assert_eq!((thresholds.yellow, thresholds.red), (2.0, 4.0));
+    }
+
+    #[test]
+    fn percentile_interpolates_and_clamps() {
+        let sorted = [10.0, 20.0, 30.0];
+
+        assert_eq!(percentile(&sorted, 0.0), 10.0);
+        assert_eq!(percentile(&sorted, 25.0), 15.0);
+        assert_eq!(percentile(&sorted, 100.0), 30.0);
+        assert_eq!(percentile(&sorted, 150.0), 30.0);
+        assert_eq!(percentile(&[7.0], 80.0), 7.0);
+    }
+
+    #[test]
+    fn colors_change_above_thresholds() {
+        let thresholds = Thresholds { yellow: 2.0, red: 4.0 };
+
+        assert!(cost_to_color(Some(2.0), &thresholds) == TariffColor::Green);
+        assert!(cost_to_color(Some(2.1), &thresholds) == TariffColor::Yellow);
+        assert!(cost_to_color(Some(4.0), &thresholds) == TariffColor::Yellow);
+        assert!(cost_to_color(Some(4.1), &thresholds) == TariffColor::Red);
+        assert!(cost_to_color(None, &thresholds) == TariffColor::Green);
+    }
+
+    #[test]
+    fn policy_rules_use_percentile_thresholds() {
+        let day_tariffs = tariffs(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let rules = PolicyRules::new(&percentile_config(), Some(&day_tariffs));
+        let tariff_map = day_tariffs.iter().map(|t| (t.x, t.y)).collect::<HashMap<DateTime<Utc>, f64>>();
+
+        // The last quarter alone is ahead, 5.0 is above the 80th percentile
+        let decision = get_policy(day_tariffs[4].x, 50, &Vec::new(), &tariff_map, 0.0, &rules);
+        assert!(decision.color == TariffColor::Red);
+
+        // The second quarter looks ahead at 2-5, avg 3.5 is between the 50th and 80th percentile
+        let decision = get_policy(day_tariffs[1].x, 50, &Vec::new(), &tariff_map, 0.0, &rules);
+        assert!(decision.color == TariffColor::Yellow);
+    }
+}
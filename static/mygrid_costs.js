@@ -0,0 +1,85 @@
+// monthly costs, revenue and self-consumed solar value
+//
+let costs_options= {
+    series: [],
+    chart: {
+        height: 250,
+        type: 'bar',
+        toolbar: {
+            show: false,
+        },
+        zoom: {
+            enabled: false,
+        },
+    },
+    colors: ["#FF4560", "#00E396", "#FEB019"],
+    fill: {
+        type:'solid',
+        opacity: 0.8,
+    },
+    dataLabels: {
+        enabled: false,
+    },
+    yaxis: {
+        min: 0,
+        axisBorder: {
+            show: false
+        },
+        axisTicks: {
+            show: false,
+        },
+        labels: {
+            show: true,
+            formatter: function (val) {
+                return Math.round(val) + " kr";
+            }
+        }
+    },
+    xaxis: {
+        position: 'bottom',
+        type: 'category',
+        axisBorder: {
+            show: false
+        },
+        axisTicks: {
+            show: true
+        },
+        labels: {
+            show: true,
+        },
+    },
+    tooltip: {
+        enabled: true,
+        y: {
+            formatter: function (val) {
+                return val.toFixed(2) + " kr";
+            }
+        }
+    },
+    legend: {
+        position: 'bottom',
+    },
+    title: {
+        text: 'Costs per month',
+        floating: true,
+        offsetY: 0,
+        align: 'center',
+    },
+    noData: {
+        text: 'Loading...'
+    },
+    theme: {
+        mode: 'dark',
+        palette: 'palette1',
+        monochrome: {
+            enabled: false,
+            color: '#255aee',
+            shadeTo: 'light',
+            shadeIntensity: 0.65
+        },
+    }
+};
+
+
+let costs = new ApexCharts(document.querySelector("#costs"), costs_options);
+costs.render();
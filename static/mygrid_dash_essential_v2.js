@@ -1,6 +1,8 @@
 const six = 360;
 const twenty_two = 1320; // 1320;
 let timer = 0;
+let selected_date = null;
+let events = null;
 
 function loadScriptSequentially(file) {
     return new Promise((resolve, reject) => {
@@ -103,125 +105,264 @@ function refreshData(forceRefresh) {
     }
     dim_screen.hide();
 
-    $.getJSON('/data/small', function(resp, textStatus, jqXHR) {
+    // Live data is pushed through the event stream, and past days don't change
+    if (!forceRefresh && (selected_date !== null || eventsOpen())) {
+        return;
+    }
+
+    const url = selected_date === null ? '/data/small' : '/data/small?date=' + selected_date;
+    $.getJSON(url, function(resp, textStatus, jqXHR) {
         const redirectUrl = jqXHR.getResponseHeader('X-Redirect-Location');
         if (redirectUrl) {
             window.location.replace(redirectUrl);
             return;
         }
 
-        let color = "LimeGreen";
-        if (resp.policy !== "Green") {
-            color = resp.policy
+        renderData(resp);
+    });
+}
+
+function renderData(resp) {
+    let color = "LimeGreen";
+    if (resp.policy === null) {
+        color = "DimGray";
+    } else if (resp.policy !== "Green") {
+        color = resp.policy
+    }
+
+    let temp_current = Math.round(resp.temp_current * 10) / 10;
+    let temp_perceived = Math.round(resp.temp_perceived * 10) / 10
+
+    let datetime = new Date();
+
+    let symbols_body = $('#symbols');
+
+    symbols_body.empty();
+    for (let i = 0; i < resp.forecast_symbol.length; i++) {
+        let row = resp.forecast_symbol[i];
+
+        let d = new Date(row.x);
+        let style = '';
+        if (!resp.historic && d.getHours() < datetime.getHours()) {
+            style = 'font-weight: bold;color: dimgray';
         }
 
-        let temp_current = Math.round(resp.temp_current * 10) / 10;
-        let temp_perceived = Math.round(resp.temp_perceived * 10) / 10
+        let time = `${String(d.getHours()).padStart(2, '0')}`;
+        symbols_body.append('<div  class="symbol">' +
+            '<p class="symbol-text" style="' + style + '">' + time + '</p>' +
+            '<img src="/symbols/' + row.y + '.webp" alt="" width="30px" height="30px">' +
+            '</div>');
+    }
 
-        let datetime = new Date();
+    $("#policy-bar").width("100%").css("background-color", color);
+    $("#policy-bar-block").attr("data-title", policyReasonText(resp.policy_reason));
+    $("#current-temp").text(temp_current + " (" + temp_perceived + ") ℃");
+    $("#minmax-today").text("Today: " + resp.today_max + " / " + resp.today_min + " ℃");
+    $("#minmax-yesterday").text("Yesterday: " + resp.yesterday_max + " / " + resp.yesterday_min + " ℃");
 
-        let symbols_body = $('#symbols');
+    temp.updateSeries(resp.temp_diagram);
 
-        symbols_body.empty();
-        for (let i = 0; i < resp.forecast_symbol.length; i++) {
-            let row = resp.forecast_symbol[i];
+    tariff_thresholds = resp.thresholds;
+    tariff_thresholds_tomorrow = resp.thresholds_tomorrow;
 
-            let d = new Date(row.x);
-            let style = '';
-            if (d.getHours() < datetime.getHours()) {
-                style = 'font-weight: bold;color: dimgray';
-            }
+    if (resp.policy_timeline != null && resp.policy_timeline.data.length > 0) {
+        $("#policy").show();
+        policy.updateSeries(policySeries(resp.policy_timeline));
+    } else {
+        $("#policy").hide();
+    }
+
+    if (resp.tariffs_buy != null) {
+        $("#tariffs-buy").show();
+        tariffs_buy.updateSeries([resp.tariffs_buy]);
+        tariffs_buy.updateOptions({ title: { text: feeSourceText(resp.fee_source) } });
+    } else {
+        $("#tariffs-buy").hide();
+    }
 
-            let time = `${String(d.getHours()).padStart(2, '0')}`;
-            symbols_body.append('<div  class="symbol">' +
-                '<p class="symbol-text" style="' + style + '">' + time + '</p>' +
-                '<img src="/symbols/' + row.y + '.webp" alt="" width="30px" height="30px">' +
-                '</div>');
+    if (resp.tariffs_buy_tomorrow != null) {
+        $("#tariffs-buy-tomorrow").show();
+        tariffs_tomorrow.updateSeries([resp.tariffs_buy_tomorrow]);
+    } else {
+        $("#tariffs-buy-tomorrow").hide();
+    }
+
+    const schedule_saves = "Scheduling saves: " + (resp.base_cost - resp.schedule_cost).toFixed(2) + "kr";
+    const today_bought = "Imported: " + resp.today_bought.toFixed(2) + "kr (" + resp.today_imported.toFixed(2) + "kWh)";
+    const today_sold = "Exported: " + resp.today_sold.toFixed(2) + "kr (" + resp.today_exported.toFixed(2) + "kWh)";
+
+    $("#schedule-saves").text(schedule_saves);
+    $("#today-bought").text(today_bought);
+    $("#today-sold").text(today_sold);
+    $("#net-cost").text("Net cost: " + resp.savings.actual_cost.toFixed(2) + "kr");
+    $("#saved-vs-grid").text("Saved vs grid only: " + resp.savings.saved_vs_grid_only.toFixed(2) + "kr");
+    $("#saved-vs-solar").text("Battery saves: " + resp.savings.saved_vs_solar_only.toFixed(2) + "kr");
+
+    let schedule_body = $('#schedule-body');
+
+    schedule_body.empty();
+    for (let i = 0; i < resp.schedule.length; i++) {
+        let row = resp.schedule[i];
+
+        const safeTrueSoc = clampSoc(row.true_soc_in);
+        const trueSocLabel = safeTrueSoc === null ? '--' : `${Math.round(safeTrueSoc)}`;
+        const socInLabel = `${row.soc_in} (${trueSocLabel})%`;
+        const socOutLabel = `${row.soc_out}%`;
+
+        let actualLabel = '--';
+        let actualTitle = '';
+        if (row.actual !== null) {
+            actualLabel = row.actual.grid_cost.toFixed(2);
+            actualTitle = `Charged ${row.actual.charged_kwh.toFixed(2)} kWh, discharged ${row.actual.discharged_kwh.toFixed(2)} kWh` +
+                (row.actual.soc_deviation === null ? '' : `, SoC ${row.actual.soc_deviation > 0 ? '+' : ''}${row.actual.soc_deviation}% vs plan`);
         }
 
-        $("#policy-bar").width("100%").css("background-color", color);
-        $("#current-temp").text(temp_current + " (" + temp_perceived + ") ℃");
-        $("#minmax-today").text("Today: " + resp.today_max + " / " + resp.today_min + " ℃");
-        $("#minmax-yesterday").text("Yesterday: " + resp.yesterday_max + " / " + resp.yesterday_min + " ℃");
+        schedule_body.append('<tr><td>' + row.block_type + '</td><td>' + row.start + '</td><td>' +
+            socInLabel + '</td><td>' + socOutLabel + '</td><td class="soc-cell">' +
+            renderSocBar(row.current_soc, row.max_soc, row.min_soc) + '</td><td>' + row.cost + '</td><td title="' + actualTitle + '">' +
+            actualLabel + '</td><td>' + row.status + '</td></tr>');
+    }
+
+    let appliances_body = $('#appliances-body');
+
+    appliances_body.empty();
+    if (resp.historic) {
+        $("#appliances").hide();
+    } else {
+        $("#appliances").show();
+        for (let i = 0; i < resp.recommendations.length; i++) {
+            let row = resp.recommendations[i];
 
-        temp.updateSeries(resp.temp_diagram);
+            appliances_body.append('<tr><td>' + row.appliance + '</td><td>' + formatTime(row.start) + '</td><td>' +
+                formatTime(row.end) + '</td><td>' + row.cost.toFixed(2) + 'kr</td><td>' +
+                (row.cost_now === null ? '--' : row.cost_now.toFixed(2) + 'kr') + '</td></tr>');
+        }
+    }
 
-        if (resp.tariffs_buy != null) {
-            $("#tariffs-buy").show();
-            tariffs_buy.updateSeries([resp.tariffs_buy]);
-        } else {
-            $("#tariffs-buy").hide();
+    const summary = resp.schedule_summary;
+    $("#schedule-summary").text(summary.blocks_ended === 0 ? '' :
+        `(followed ${summary.blocks_followed}/${summary.blocks_ended}, cost ${summary.actual_cost.toFixed(2)}kr vs planned ${summary.planned_cost.toFixed(2)}kr)`);
+
+    markStale(resp.sources, 'inverter', '#policy-bar-block, #today-bought, #today-sold, #net-cost, #saved-vs-grid, #saved-vs-solar');
+    markStale(resp.sources, 'weather', '#current-temp, .minmax, #symbols, #temperature');
+    markStale(resp.sources, 'nordpool', '#tariffs-buy, #tariffs-buy-tomorrow, #policy, #appliances-body');
+    markStale(resp.sources, 'mygrid', '#schedule-saves, #schedule-body');
+
+    $("#version").text("Version: " + resp.version);
+    $("#day-picker").val(resp.date).attr("max", todayString(resp.time_delta));
+    
+    let coeff = 1000 * 60 * 15;
+    let datetime_quarters = new Date(Math.floor((datetime.getTime() - resp.time_delta) / coeff) * coeff);
+
+    tariffs_buy.updateOptions({
+        annotations: {
+            xaxis: resp.historic ? [] : [
+                {
+                    x: datetime_quarters.getTime(),
+                },
+            ]
+        }
+    });
+    temp.updateOptions({
+        annotations: {
+            xaxis: resp.historic ? [] : [
+                {
+                    x: datetime.getTime() - resp.time_delta,
+                },
+            ]
         }
+    });
+    tariffs_buy.updateOptions({
+        yaxis: {
+            min: 0,
+            max: resp.max_tariff,
+        }
+    });
+    tariffs_tomorrow.updateOptions({
+        yaxis: {
+            min: 0,
+            max: resp.max_tariff,
+        }
+    });
+}
 
-        if (resp.tariffs_buy_tomorrow != null) {
-            $("#tariffs-buy-tomorrow").show();
-            tariffs_tomorrow.updateSeries([resp.tariffs_buy_tomorrow]);
-        } else {
-            $("#tariffs-buy-tomorrow").hide();
+function eventsOpen() {
+    return events !== null && events.readyState === EventSource.OPEN;
+}
+
+function connectEvents() {
+    events = new EventSource('/events/small');
+    events.onmessage = (event) => {
+        if (selected_date === null) {
+            renderData(JSON.parse(event.data));
         }
+    };
+    events.onerror = () => {
+        // The stream is gone, e.g. due to an expired session, so fall back to a regular
+        // request (which may redirect to login) and try to reconnect in a while
+        events.close();
+        setTimeout(() => {
+            refreshData(true);
+            connectEvents();
+        }, 60000);
+    };
+}
 
-        const schedule_saves = "Scheduling saves: " + (resp.base_cost - resp.schedule_cost).toFixed(2) + "kr";
-        const today_bought = "Imported: " + resp.today_bought.toFixed(2) + "kr (" + resp.today_imported.toFixed(2) + "kWh)";
-        const today_sold = "Exported: " + resp.today_sold.toFixed(2) + "kr (" + resp.today_exported.toFixed(2) + "kWh)";
+function formatTime(timestamp) {
+    let d = new Date(timestamp);
+    let today = new Date();
+    let day = d.getDate() === today.getDate() ? '' : 'Tomorrow ';
 
-        $("#schedule-saves").text(schedule_saves);
-        $("#today-bought").text(today_bought);
-        $("#today-sold").text(today_sold);
+    return day + `${String(d.getHours()).padStart(2, '0')}:${String(d.getMinutes()).padStart(2, '0')}`;
+}
 
-        let schedule_body = $('#schedule-body');
+function findBestTime() {
+    const power = $("#appliance-power").val();
+    const duration = $("#appliance-duration").val();
 
-        schedule_body.empty();
-        for (let i = 0; i < resp.schedule.length; i++) {
-            let row = resp.schedule[i];
+    $.getJSON('/recommend', { power: power, duration: duration }, function(resp, textStatus, jqXHR) {
+        const redirectUrl = jqXHR.getResponseHeader('X-Redirect-Location');
+        if (redirectUrl) {
+            window.location.replace(redirectUrl);
+            return;
+        }
+
+        $("#appliance-result").text(formatTime(resp.start) + ' - ' + formatTime(resp.end) + ', ' + resp.cost.toFixed(2) + 'kr' +
+            (resp.cost_now === null ? '' : ' (now ' + resp.cost_now.toFixed(2) + 'kr)'));
+    }).fail(function() {
+        $("#appliance-result").text('No window found');
+    });
+}
 
-            const safeTrueSoc = clampSoc(row.true_soc_in);
-            const trueSocLabel = safeTrueSoc === null ? '--' : `${Math.round(safeTrueSoc)}`;
-            const socInLabel = `${row.soc_in} (${trueSocLabel})%`;
-            const socOutLabel = `${row.soc_out}%`;
+function markStale(sources, source, selector) {
+    const status = sources == null ? null : sources[source];
+    const stale = status != null && status.stale;
 
-            schedule_body.append('<tr><td>' + row.block_type + '</td><td>' + row.start + '</td><td>' +
-                socInLabel + '</td><td>' + socOutLabel + '</td><td class="soc-cell">' +
-                renderSocBar(row.current_soc, row.max_soc, row.min_soc) + '</td><td>' + row.cost + '</td><td>' + row.status + '</td></tr>');
+    let title = '';
+    if (stale) {
+        title = status.last_success == null ? 'No data yet' : 'Last updated ' + new Date(status.last_success).toLocaleString();
+        if (status.last_error != null) {
+            title += ': ' + status.last_error;
         }
+    }
 
-        $("#version").text("Version: " + resp.version);
-        
-        let coeff = 1000 * 60 * 15;
-        let datetime_quarters = new Date(Math.floor((datetime.getTime() - resp.time_delta) / coeff) * coeff);
-
-        tariffs_buy.updateOptions({
-            annotations: {
-                xaxis: [
-                    {
-                        x: datetime_quarters.getTime(),
-                    },
-                ]
-            }
-        });
-        temp.updateOptions({
-            annotations: {
-                xaxis: [
-                    {
-                        x: datetime.getTime() - resp.time_delta,
-                    },
-                ]
-            }
-        });
-        tariffs_buy.updateOptions({
-            yaxis: {
-                min: 0,
-                max: resp.max_tariff,
-            }
-        });
-        tariffs_tomorrow.updateOptions({
-            yaxis: {
-                min: 0,
-                max: resp.max_tariff,
-            }
-        });
+    // An element's own tooltip is kept in data-title and combined with the staleness text
+    $(selector).toggleClass('stale', stale).each(function () {
+        const own = $(this).attr('data-title') || '';
+        $(this).attr('title', [own, title].filter(t => t !== '').join('\n'));
     });
 }
 
+function todayString(time_delta) {
+    const d = new Date(new Date().getTime() - time_delta);
+    return `${d.getFullYear()}-${String(d.getMonth() + 1).padStart(2, '0')}-${String(d.getDate()).padStart(2, '0')}`;
+}
+
+function selectDate(value) {
+    selected_date = value === '' ? null : value;
+    refreshData(true);
+}
+
 function undimScreen() {
     clearInterval(timer);
 
@@ -239,8 +380,10 @@ loadScriptSequentially('locale_se.js')
     .then(() => loadScriptSequentially('mygrid_temp.js'))
     .then(() => loadScriptSequentially('mygrid_tariffs.js'))
     .then(() => loadScriptSequentially('mygrid_tariffs_tomorrow.js'))
+    .then(() => loadScriptSequentially('mygrid_policy.js'))
     .then(() => {
         refreshData(true);
+        connectEvents();
         timer = setInterval(() => {
             refreshData(false);
         }, 60000);
@@ -16,86 +16,203 @@ function loadScriptSequentially(file) {
     });
 }
 
+let selected_date = null;
+let events = null;
+
 function refreshData() {
-    $.getJSON('/data/full', function(resp, textStatus, jqXHR) {
+    const url = selected_date === null ? '/data/full' : '/data/full?date=' + selected_date;
+    $.getJSON(url, function(resp, textStatus, jqXHR) {
         const redirectUrl = jqXHR.getResponseHeader('X-Redirect-Location');
         if (redirectUrl) {
             window.location.replace(redirectUrl);
             return;
         }
 
-        let color = "LimeGreen";
-        if (resp.policy !== "Green") {
-            color = resp.policy
-        }
+        renderData(resp);
+    });
+}
+
+function renderData(resp) {
+    let color = "LimeGreen";
+    if (resp.policy === null) {
+        color = "DimGray";
+    } else if (resp.policy !== "Green") {
+        color = resp.policy
+    }
 
-        let temp_current = Math.round(resp.temp_current * 10) / 10;
-        let temp_perceived = Math.round(resp.temp_perceived * 10) / 10
+    let temp_current = Math.round(resp.temp_current * 10) / 10;
+    let temp_perceived = Math.round(resp.temp_perceived * 10) / 10
 
-        $("#policy-bar").width("100%").css("background-color", color);
-        $("#current-temp").text(temp_current + " (" + temp_perceived + ") ℃");
-        $("#minmax-today").text("Today: " + resp.today_max + " / " + resp.today_min + " ℃");
-        $("#minmax-yesterday").text("Yesterday: " + resp.yesterday_max + " / " + resp.yesterday_min + " ℃");
-        
+    $("#policy-bar").width("100%").css("background-color", color);
+    $("#policy-bar-block").attr("data-title", policyReasonText(resp.policy_reason));
+    $("#current-temp").text(temp_current + " (" + temp_perceived + ") ℃");
+    $("#minmax-today").text("Today: " + resp.today_max + " / " + resp.today_min + " ℃");
+    $("#minmax-yesterday").text("Yesterday: " + resp.yesterday_max + " / " + resp.yesterday_min + " ℃");
+    
+    if (resp.current_prod_load != null && resp.current_soc_soh != null) {
+        $("#realtime-box").show();
         realtime.updateSeries([resp.current_prod_load]);
         soc.updateSeries([resp.current_soc_soh]);
+    } else {
+        $("#realtime-box").hide();
+    }
+
+    tariff_thresholds = resp.thresholds;
+
+    if (resp.policy_timeline != null && resp.policy_timeline.data.length > 0) {
+        $("#policy").show();
+        policy.updateSeries(policySeries(resp.policy_timeline));
+    } else {
+        $("#policy").hide();
+    }
+
+    if (resp.tariffs_buy != null) {
+        $("#tariffs-buy").show();
+        tariffs_buy.updateSeries([resp.tariffs_buy]);
+        tariffs_buy.updateOptions({ title: { text: feeSourceText(resp.fee_source) } });
+    } else {
+        $("#tariffs-buy").hide();
+    }
+
+    production.updateSeries(resp.prod_diagram);
+    load.updateSeries(resp.load_diagram);
+    production.updateOptions({ title: { text: 'Power Production' + accuracyText(resp.forecast_accuracy, 'production') } });
+    load.updateOptions({ title: { text: 'Power Load' + accuracyText(resp.forecast_accuracy, 'load') } });
+    cloud.updateSeries([resp.cloud_diagram]);
+    temp.updateSeries(resp.temp_diagram);
+
+    markStale(resp.sources, 'inverter', '#policy-bar-block, #realtime-box');
+    markStale(resp.sources, 'weather', '#current-temp, .minmax, #temperature');
+    markStale(resp.sources, 'nordpool', '#tariffs-buy, #policy');
+    markStale(resp.sources, 'mygrid', '#mygrid, #cloud-factor');
+
+    $("#day-picker").val(resp.date).attr("max", todayString(resp.time_delta));
+
+    let datetime = new Date();
+    let coeff = 1000 * 60 * 15;
+    let datetime_quarters = new Date(Math.floor((datetime.getTime() - resp.time_delta) / coeff) * coeff);
 
-        if (resp.tariffs_buy != null) {
-            $("#tariffs-buy").show();
-            tariffs_buy.updateSeries([resp.tariffs_buy]);
-        } else {
-            $("#tariffs-buy").hide();
+    tariffs_buy.updateOptions({
+        annotations: {
+            xaxis: resp.historic ? [] : [
+                {
+                    x: datetime_quarters.getTime(),
+                },
+            ]
         }
+    });
+    temp.updateOptions({
+        annotations: {
+            xaxis: resp.historic ? [] : [
+                {
+                    x: datetime.getTime() - resp.time_delta,
+                },
+            ]
+        }
+    });
+    tariffs_buy.updateOptions({
+        yaxis: {
+            min: 0,
+            max: resp.max_tariff,
+        }
+    });
+}
 
-        production.updateSeries(resp.prod_diagram);
-        load.updateSeries(resp.load_diagram);
-        cloud.updateSeries([resp.cloud_diagram]);
-        temp.updateSeries(resp.temp_diagram);
-
-        let datetime = new Date();
-        let coeff = 1000 * 60 * 15;
-        let datetime_quarters = new Date(Math.floor((datetime.getTime() - resp.time_delta) / coeff) * coeff);
-
-        tariffs_buy.updateOptions({
-            annotations: {
-                xaxis: [
-                    {
-                        x: datetime_quarters.getTime(),
-                    },
-                ]
-            }
-        });
-        temp.updateOptions({
-            annotations: {
-                xaxis: [
-                    {
-                        x: datetime.getTime() - resp.time_delta,
-                    },
-                ]
-            }
-        });
-        tariffs_buy.updateOptions({
-            yaxis: {
-                min: 0,
-                max: resp.max_tariff,
-            }
+function refreshCosts() {
+    $.getJSON('/aggregate/month', function(resp, textStatus, jqXHR) {
+        const redirectUrl = jqXHR.getResponseHeader('X-Redirect-Location');
+        if (redirectUrl) {
+            window.location.replace(redirectUrl);
+            return;
+        }
+
+        const series = (name, value) => ({
+            name: name,
+            data: resp.buckets.map(b => ({ x: b.label, y: value(b.totals) })),
         });
+
+        costs.updateSeries([
+            series("Import cost", t => t.cost),
+            series("Export revenue", t => t.revenue),
+            series("Self-consumed solar", t => t.self_consumed_value),
+        ]);
     });
 }
 
+function connectEvents() {
+    events = new EventSource('/events/full');
+    events.onmessage = (event) => {
+        if (selected_date === null) {
+            renderData(JSON.parse(event.data));
+        }
+    };
+    events.onerror = () => {
+        // The stream is gone, e.g. due to an expired session, so fall back to a regular
+        // request (which may redirect to login) and try to reconnect in a while
+        events.close();
+        setTimeout(() => {
+            refreshData();
+            connectEvents();
+        }, 60000);
+    };
+}
+
+function accuracyText(accuracy, series) {
+    if (accuracy == null || accuracy[series] == null) {
+        return '';
+    }
+    let stats = accuracy[series];
+    let mape = stats.mape != null ? ", MAPE " + Math.round(stats.mape) + " %" : "";
+
+    return " (MAE " + stats.mae.toFixed(2) + " kW" + mape + ", bias " + stats.bias.toFixed(2) + " kW)";
+}
+
+function markStale(sources, source, selector) {
+    const status = sources == null ? null : sources[source];
+    const stale = status != null && status.stale;
+
+    let title = '';
+    if (stale) {
+        title = status.last_success == null ? 'No data yet' : 'Last updated ' + new Date(status.last_success).toLocaleString();
+        if (status.last_error != null) {
+            title += ': ' + status.last_error;
+        }
+    }
+
+    // An element's own tooltip is kept in data-title and combined with the staleness text
+    $(selector).toggleClass('stale', stale).each(function () {
+        const own = $(this).attr('data-title') || '';
+        $(this).attr('title', [own, title].filter(t => t !== '').join('\n'));
+    });
+}
+
+function todayString(time_delta) {
+    const d = new Date(new Date().getTime() - time_delta);
+    return `${d.getFullYear()}-${String(d.getMonth() + 1).padStart(2, '0')}-${String(d.getDate()).padStart(2, '0')}`;
+}
+
+function selectDate(value) {
+    selected_date = value === '' ? null : value;
+    refreshData();
+}
+
 loadScriptSequentially('locale_se.js')
     .then(() => loadScriptSequentially('mygrid_realtime.js'))
     .then(() => loadScriptSequentially('mygrid_soc_soh.js'))
     .then(() => loadScriptSequentially('mygrid_tariffs.js'))
+    .then(() => loadScriptSequentially('mygrid_policy.js'))
     .then(() => loadScriptSequentially('mygrid_prod.js'))
     .then(() => loadScriptSequentially('mygrid_load.js'))
     .then(() => loadScriptSequentially('mygrid_cloud.js'))
     .then(() => loadScriptSequentially('mygrid_temp.js'))
+    .then(() => loadScriptSequentially('mygrid_costs.js'))
     .then(() => {
         refreshData();
+        refreshCosts();
+        connectEvents();
         setInterval(() => {
-            refreshData();
-        }, 60000);
+            refreshCosts();
+        }, 3600000);
     })
     .catch(error => displayMessage(error.message, 'error'));
 
@@ -1,9 +1,18 @@
-// Usage Policy
+// Usage Policy timeline
 //
+const policy_colors = {
+    Green: "#00E396",
+    Yellow: "#FEB019",
+    Red: "#FF4560",
+};
+
+// Policy decisions behind the bars, for the tooltip
+let policy_decisions = [];
+
 let policy_options = {
     series: [],
     chart: {
-        height: 200,
+        height: 90,
         type: 'bar',
         toolbar: {
             show: false,
@@ -15,68 +24,52 @@ let policy_options = {
     legend: {
         show: false,
     },
-    colors: [function({ value }) {
-            if (value <= 20) {
-                return "#FF4560"
-            } else if (value > 20 && value < 70) {
-                return "#FEB019"
-            } else {
-                return "#00E396"
-            }
-        }],
-    stroke: {
-        show: true,
-        width: 2,
-    },
     fill: {
         type:'solid',
-        opacity: 0.7,
+        opacity: 0.8,
     },
     plotOptions: {
         bar: {
-            columnWidth: '50%',
-            dataLabels: {
-                position: 'top',
-            }
+            columnWidth: '100%',
         }
     },
     dataLabels: {
-        enabled: true,
-        formatter: function(value) {
-            return value + "%";
-        },
+        enabled: false,
     },
     yaxis: {
         min: 0,
-        max: 100,
-        axisBorder: {
-            show: false
-        },
-        axisTicks: {
-            show: false,
-        },
+        max: 1,
         labels: {
-            show: true,
-            formatter: function (val) {
-                return val + "%";
-            }
+            show: false,
+            minWidth: 30,
         }
     },
     xaxis: {
         position: 'bottom',
-        type: 'category',
+        type: 'datetime',
         axisBorder: {
             show: false
         },
         axisTicks: {
-            show: false
+            show: true
         },
         labels: {
             show: true,
+            datetimeUTC: false,
         },
     },
+    grid: {
+        show: false,
+    },
     tooltip: {
-        enabled: false,
+        enabled: true,
+        custom: function({ dataPointIndex }) {
+            const decision = policy_decisions[dataPointIndex];
+            if (decision == null) {
+                return '';
+            }
+            return '<div style="padding: 4px 8px">' + decision.color + ': ' + policyReasonText(decision.reason) + '</div>';
+        },
     },
     title: {
         text: 'Policy',
@@ -99,6 +92,43 @@ let policy_options = {
     }
 };
 
+// Turns the policy timeline into bars of equal height colored by policy
+function policySeries(timeline) {
+    policy_decisions = timeline.data.map(d => d.y);
+
+    return [{
+        name: timeline.name,
+        data: timeline.data.map(d => ({ x: d.x, y: 1, fillColor: policy_colors[d.y.color] })),
+    }];
+}
+
+// Returns a human readable explanation of a policy reason
+function policyReasonText(reason) {
+    if (reason == null) {
+        return '';
+    }
+    const price = (value) => value == null ? 'unknown' : value.toFixed(2) + ' kr';
+
+    switch (reason.kind) {
+        case 'UseLowSoc':
+            return `Use block, SoC ${reason.soc}% below ${reason.min_soc}%, tariff avg ${price(reason.tariff_avg)} ahead`;
+        case 'UseCharged':
+            return `Use block, battery charged at avg ${price(reason.charge_avg)}`;
+        case 'UseNotCharged':
+            return 'Use block, battery not charged from grid';
+        case 'HoldNotImporting':
+            return `Hold block, grid ${reason.grid_power.toFixed(1)} kW`;
+        case 'HoldImporting':
+            return `Hold block, importing ${Math.abs(reason.grid_power).toFixed(1)} kW, tariff avg ${price(reason.tariff_avg)} ahead`;
+        case 'Tariff':
+            return (reason.block_type == null ? 'No block' : reason.block_type + ' block') + `, tariff avg ${price(reason.tariff_avg)} ahead`;
+        case 'PowerPeak':
+            return `Hour import heading for ${reason.projected.toFixed(1)} kW, ` +
+                (reason.peak == null ? 'first peak of month' : `month peak ${reason.peak.toFixed(1)} kW`);
+        default:
+            return '';
+    }
+}
 
 let policy = new ApexCharts(document.querySelector("#policy"), policy_options);
 policy.render();
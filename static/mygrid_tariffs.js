@@ -1,5 +1,7 @@
 // tariffs buy
 //
+let tariff_thresholds = { yellow: 2, red: 4 };
+
 let tariffs_buy_options= {
     series: [],
     chart: {
@@ -14,9 +16,9 @@ let tariffs_buy_options= {
     },
     colors: [
         function({ value }) {
-            if (value <= 2) {
+            if (value <= tariff_thresholds.yellow) {
                 return "#00E396"
-            } else if (value > 2 && value <= 4) {
+            } else if (value > tariff_thresholds.yellow && value <= tariff_thresholds.red) {
                 return "#FEB019"
             } else {
                 return "#FF4560"
@@ -85,5 +87,16 @@ let tariffs_buy_options= {
 };
 
 
+function feeSourceText(source) {
+    switch (source) {
+        case 'mygrid':
+            return 'Tariffs (fees from MyGrid)';
+        case 'config':
+            return 'Tariffs (fees from config)';
+        default:
+            return 'Tariffs (no fees)';
+    }
+}
+
 let tariffs_buy = new ApexCharts(document.querySelector("#tariffs-buy"), tariffs_buy_options);
 tariffs_buy.render();
@@ -1,5 +1,7 @@
 // tariffs buy tomorrow
 //
+let tariff_thresholds_tomorrow = { yellow: 2, red: 4 };
+
 let tariffs_tomorrow_options= {
     series: [],
     chart: {
@@ -14,9 +16,9 @@ let tariffs_tomorrow_options= {
     },
     colors: [
         function({ value }) {
-            if (value <= 2) {
+            if (value <= tariff_thresholds_tomorrow.yellow) {
                 return "#00E396"
-            } else if (value > 2 && value <= 4) {
+            } else if (value > tariff_thresholds_tomorrow.yellow && value <= tariff_thresholds_tomorrow.red) {
                 return "#FEB019"
             } else {
                 return "#FF4560"